@@ -0,0 +1,401 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{read, read_dir, read_to_string, remove_dir, remove_file, symlink_metadata, write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+use twox_hash::XxHash64;
+
+use crate::helpers::console::Console;
+use crate::helpers::depfile_tools::read_depfile;
+use crate::helpers::file_tools::is_within;
+
+/// Name of the file, stored in each project's output directory, that remembers what was built last time.
+pub const BUILD_STATE_FILE: &str = "spbuild_state.json";
+
+/// What was used to produce a single object file.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct CompileRecord {
+    /// Hash of the source file contents.
+    pub source_hash: u64,
+    /// The exact compiler command line (program included).
+    pub command: Vec<String>,
+    /// Absolute path to the produced object file.
+    pub object: PathBuf,
+    /// Hash of every header the source included, as reported by the compiler's depfile.
+    /// `None` for a header that could not be read once compiled, which makes the source outdated until it is rebuilt.
+    #[serde(default)]
+    pub headers: BTreeMap<PathBuf, Option<u64>>,
+}
+
+/// What was used to produce the final artifact of a project (executable, library...).
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct LinkRecord {
+    /// The exact linker command line (program included).
+    pub command: Vec<String>,
+    /// Hash of every input file passed to the linker.
+    pub inputs: BTreeMap<PathBuf, u64>,
+    /// Absolute path to the produced artifact.
+    pub output: PathBuf,
+}
+
+/// Persistent incremental build state of a single project.
+#[derive(Serialize, Deserialize, Default)]
+pub struct BuildState {
+    /// Compile records, keyed by absolute source path.
+    #[serde(default)]
+    pub compiled: BTreeMap<PathBuf, CompileRecord>,
+    /// Last successful link, if any.
+    #[serde(default)]
+    pub link: Option<LinkRecord>,
+    /// Other files written for the project: import libraries, shared library symlinks, copied DLLs, linker side files...
+    #[serde(default)]
+    pub produced: BTreeSet<PathBuf>,
+}
+
+impl BuildState {
+    ///
+    /// Loads the build state stored in `output_dir`.
+    /// A missing or unreadable state file simply means that everything has to be rebuilt.
+    ///
+    pub fn load(output_dir: &Path) -> BuildState {
+        let state_path = output_dir.join(BUILD_STATE_FILE);
+
+        let contents = match read_to_string(&state_path) {
+            Ok(c) => c,
+            Err(_) => return BuildState::default(),
+        };
+
+        serde_json::from_str(&contents).unwrap_or_else(|e| {
+            Console::log_warning(format!("Ignoring corrupted build state {}: {}", state_path.display(), e).as_str());
+            BuildState::default()
+        })
+    }
+
+    /// Writes the build state to `output_dir`.
+    pub fn save(&self, output_dir: &Path) -> Result<(), &'static str> {
+        let contents = serde_json::to_string_pretty(self).map_err(|_| "Failed to serialize build state")?;
+        write(output_dir.join(BUILD_STATE_FILE), contents).map_err(|_| "Failed to write build state")
+    }
+
+    ///
+    /// Checks whether `source` has to be recompiled.
+    /// A source is up to date when its contents, the headers it included and the compiler command
+    /// did not change since the last successful compilation, and the object file still exists.
+    ///
+    pub fn is_compile_up_to_date(&self, source: &Path, source_hash: u64, command: &[String]) -> bool {
+        match self.compiled.get(source) {
+            Some(record) => {
+                record.source_hash == source_hash
+                    && record.command == command
+                    && record.object.exists()
+                    && record.headers.iter().all(|(header, hash)| hash.is_some() && hash_file(header) == *hash)
+            }
+            None => false,
+        }
+    }
+
+    ///
+    /// Checks whether the link step has to be re-run.
+    /// The link is up to date when the command and every input are unchanged and the output still exists.
+    ///
+    pub fn is_link_up_to_date(&self, record: &LinkRecord) -> bool {
+        match &self.link {
+            Some(previous) => previous == record && previous.output.exists(),
+            None => false,
+        }
+    }
+}
+
+///
+/// Deletes the files recorded in the build state of `output_dir` (objects and their depfiles, the link output,
+/// the other produced files and the state itself), then the directories this left empty, up to `root` excluded.
+/// Anything else in `output_dir` is left alone, and so are recorded files outside of it (from a copied or edited state).
+/// # Arguments
+/// * `output_dir` - The project's output directory.
+/// * `root` - The directory above which empty directories are kept (the solution root).
+/// * `verbose` - Whether to enable verbose output.
+/// # Returns
+/// * The number of files deleted, or an error if one of them could not be deleted.
+///
+pub fn clean_output_dir(output_dir: &Path, root: &Path, verbose: bool) -> Result<usize, &'static str> {
+    let state_path = output_dir.join(BUILD_STATE_FILE);
+    if !state_path.exists() {
+        return Ok(0);
+    }
+    let output_dir = output_dir.canonicalize().map_err(|_| "Failed to canonicalize output directory")?;
+
+    let state = BuildState::load(&output_dir);
+    let mut files: BTreeSet<PathBuf> = BTreeSet::new();
+    for record in state.compiled.values() {
+        // Both backends write the depfile next to the object.
+        files.insert(record.object.clone());
+        files.insert(record.object.with_extension("d"));
+    }
+    files.extend(state.link.map(|l| l.output));
+    files.extend(state.produced);
+    files.insert(state_path);
+
+    let mut removed = 0;
+    for file in &files {
+        // symlink_metadata, so dangling symlinks are removed too.
+        if symlink_metadata(file).is_err() {
+            continue;
+        }
+        if !is_within(file, &output_dir) {
+            Console::log_warning(format!("Not removing {}: it is outside of {}", file.display(), output_dir.display()).as_str());
+            continue;
+        }
+        Console::log_verbose(format!("Removing {}", file.display()).as_str(), verbose);
+        remove_file(file).map_err(|_| "Failed to remove a build output")?;
+        removed += 1;
+
+        let mut dir = file.parent();
+        while let Some(current) = dir {
+            if current == root || !current.starts_with(root) || !is_empty_dir(current) {
+                break;
+            }
+            remove_dir(current).map_err(|_| "Failed to remove an empty output directory")?;
+            dir = current.parent();
+        }
+    }
+
+    Ok(removed)
+}
+
+///
+/// Finds the output directories of a project that hold a build state, whatever profile, platform and architecture
+/// they were built for: `<solution_root>/output/<profile>/<target>/<project_path>` (see `project_output_dir`).
+/// Profiles and architectures since removed from the solution are found too.
+///
+pub fn recorded_output_dirs(solution_root: &Path, project_path: &Path) -> Vec<PathBuf> {
+    let subdirs = |dir: &Path| -> Vec<PathBuf> {
+        read_dir(dir)
+            .map(|entries| entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()).collect())
+            .unwrap_or_default()
+    };
+
+    let mut dirs: Vec<PathBuf> = subdirs(&solution_root.join("output"))
+        .iter()
+        .flat_map(|profile| subdirs(profile))
+        .map(|target| target.join(project_path))
+        .filter(|dir| dir.join(BUILD_STATE_FILE).is_file())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+fn is_empty_dir(dir: &Path) -> bool {
+    read_dir(dir).is_ok_and(|mut entries| entries.next().is_none())
+}
+
+/// Hashes the contents of a file. Returns `None` if the file cannot be read.
+pub fn hash_file(path: &Path) -> Option<u64> {
+    read(path).ok().map(|bytes| XxHash64::oneshot(0, &bytes))
+}
+
+/// Hashes every file of `paths`, failing if one of them cannot be read.
+pub fn hash_files(paths: &[PathBuf]) -> Result<BTreeMap<PathBuf, u64>, &'static str> {
+    let mut hashes = BTreeMap::new();
+    for path in paths {
+        let hash = hash_file(path).ok_or("Failed to read file for hashing")?;
+        hashes.insert(path.clone(), hash);
+    }
+    Ok(hashes)
+}
+
+///
+/// Hashes the headers listed in a depfile, leaving out the source file itself.
+/// A header that vanished since the compilation gets no hash, so the next build recompiles the source.
+///
+pub fn hash_headers(depfile_path: &Path, source: &Path) -> BTreeMap<PathBuf, Option<u64>> {
+    read_depfile(depfile_path)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|header| header != source)
+        .map(|header| {
+            let hash = hash_file(&header);
+            (header, hash)
+        })
+        .collect()
+}
+
+/// Returns the full command line (program followed by its arguments) of `command`.
+pub fn command_line(command: &Command) -> Vec<String> {
+    let mut line = vec![command.get_program().to_string_lossy().into_owned()];
+    line.extend(command.get_args().map(|a| a.to_string_lossy().into_owned()));
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+    use std::path::{Path, PathBuf};
+    use crate::test_fixtures::temp_dir;
+    use super::{clean_output_dir, hash_file, hash_headers, recorded_output_dirs, BuildState, CompileRecord, LinkRecord, BUILD_STATE_FILE};
+
+    fn record(object: &Path, source_hash: u64, headers: BTreeMap<PathBuf, Option<u64>>) -> CompileRecord {
+        CompileRecord { source_hash, command: vec!["gcc".to_string(), "-c".to_string()], object: object.to_path_buf(), headers }
+    }
+
+    #[test]
+    fn state_round_trips_and_tolerates_missing_or_corrupted_files() {
+        let dir = temp_dir("state");
+
+        let missing = BuildState::load(&dir);
+        assert!(missing.compiled.is_empty() && missing.link.is_none());
+
+        let mut state = BuildState::default();
+        state.compiled.insert(PathBuf::from("/src/main.c"), record(Path::new("/out/main.c.o"), 42, BTreeMap::new()));
+        state.link = Some(LinkRecord { command: vec!["g++".to_string()], inputs: BTreeMap::new(), output: PathBuf::from("/out/app") });
+        state.produced.insert(PathBuf::from("/out/libapp.so"));
+        state.save(&dir).unwrap();
+
+        let loaded = BuildState::load(&dir);
+        let round_trips = loaded.compiled == state.compiled && loaded.link == state.link && loaded.produced == state.produced;
+
+        write(dir.join(BUILD_STATE_FILE), "{ not json").unwrap();
+        let corrupted_is_empty = BuildState::load(&dir).compiled.is_empty();
+        remove_dir_all(&dir).unwrap();
+
+        assert!(round_trips);
+        assert!(corrupted_is_empty);
+    }
+
+    #[test]
+    fn compile_is_up_to_date_only_when_nothing_changed() {
+        let dir = temp_dir("up_to_date");
+        let object = dir.join("main.c.o");
+        let header = dir.join("foo.h");
+        write(&object, "").unwrap();
+        write(&header, "#define FOO 1\n").unwrap();
+
+        let source = PathBuf::from("/src/main.c");
+        let command = vec!["gcc".to_string(), "-c".to_string()];
+        let mut state = BuildState::default();
+        state.compiled.insert(source.clone(), record(&object, 1, BTreeMap::from([(header.clone(), hash_file(&header))])));
+
+        let unchanged = state.is_compile_up_to_date(&source, 1, &command);
+        let source_changed = state.is_compile_up_to_date(&source, 2, &command);
+        let command_changed = state.is_compile_up_to_date(&source, 1, &["clang".to_string(), "-c".to_string()]);
+        let unknown_source = state.is_compile_up_to_date(Path::new("/src/other.c"), 1, &command);
+        write(&header, "#define FOO 2\n").unwrap();
+        let header_changed = state.is_compile_up_to_date(&source, 1, &command);
+        remove_dir_all(&dir).unwrap();
+        let object_missing = state.is_compile_up_to_date(&source, 1, &command);
+
+        assert!(unchanged);
+        assert!(!source_changed);
+        assert!(!command_changed);
+        assert!(!unknown_source);
+        assert!(!header_changed);
+        assert!(!object_missing);
+    }
+
+    #[test]
+    fn vanished_headers_make_the_source_outdated() {
+        let dir = temp_dir("vanished_header");
+        let object = dir.join("main.c.o");
+        let (header, gone) = (dir.join("foo.h"), dir.join("gone.h"));
+        write(&object, "").unwrap();
+        write(&header, "#define FOO 1\n").unwrap();
+        let source = dir.join("main.c");
+        let depfile = dir.join("main.c.d");
+        write(&depfile, format!("{}: {} {} {}\n", object.display(), source.display(), header.display(), gone.display())).unwrap();
+
+        let headers = hash_headers(&depfile, &source);
+        let header_hash = hash_file(&header);
+        let mut state = BuildState::default();
+        state.compiled.insert(source.clone(), record(&object, 1, headers.clone()));
+        let command = vec!["gcc".to_string(), "-c".to_string()];
+        let up_to_date = state.is_compile_up_to_date(&source, 1, &command);
+        // Even once the header is back, the object may have been built without it
+        write(&gone, "").unwrap();
+        let up_to_date_once_back = state.is_compile_up_to_date(&source, 1, &command);
+        remove_dir_all(&dir).unwrap();
+
+        assert_eq!(headers, BTreeMap::from([(header, header_hash), (gone, None)]));
+        assert!(header_hash.is_some());
+        assert!(!up_to_date);
+        assert!(!up_to_date_once_back);
+    }
+
+    #[test]
+    fn link_is_up_to_date_only_when_nothing_changed() {
+        let dir = temp_dir("link_up_to_date");
+        let output = dir.join("app");
+        write(&output, "").unwrap();
+
+        let link = LinkRecord { command: vec!["g++".to_string()], inputs: BTreeMap::from([(PathBuf::from("/out/main.c.o"), 1)]), output: output.clone() };
+        let state = BuildState { link: Some(link.clone()), ..Default::default() };
+
+        let unchanged = state.is_link_up_to_date(&link);
+        let input_changed = state.is_link_up_to_date(&LinkRecord { inputs: BTreeMap::from([(PathBuf::from("/out/main.c.o"), 2)]), ..link.clone() });
+        remove_dir_all(&dir).unwrap();
+        let output_missing = state.is_link_up_to_date(&link);
+
+        assert!(unchanged);
+        assert!(!input_changed);
+        assert!(!output_missing);
+        assert!(!BuildState::default().is_link_up_to_date(&link));
+    }
+
+    #[test]
+    fn clean_removes_only_recorded_files() {
+        let root = temp_dir("clean").canonicalize().unwrap();
+        let output_dir = root.join("output").join("app");
+        create_dir_all(output_dir.join("src")).unwrap();
+        create_dir_all(root.join("output").join("other")).unwrap();
+
+        let object = output_dir.join("src").join("main.c.o");
+        let outside = [root.join("main.c"), output_dir.join("..").join("other").join("lib.a")];
+        for file in [&object, &object.with_extension("d"), &output_dir.join("app"), &output_dir.join("notes.txt")].into_iter().chain(&outside) {
+            write(file, "").unwrap();
+        }
+
+        let mut state = BuildState::default();
+        state.compiled.insert(PathBuf::from("/src/main.c"), CompileRecord { source_hash: 0, command: Vec::new(), object, headers: BTreeMap::new() });
+        state.link = Some(LinkRecord { command: Vec::new(), inputs: BTreeMap::new(), output: output_dir.join("app") });
+        state.produced.insert(output_dir.join("libgone.dll"));
+        state.produced.extend(outside.iter().cloned());
+        state.save(&output_dir).unwrap();
+
+        let removed = clean_output_dir(&output_dir, &root, false).unwrap();
+        let src_dir_removed = !output_dir.join("src").exists();
+        let notes_kept = output_dir.join("notes.txt").exists();
+        let outside_kept = outside.iter().all(|f| f.exists());
+        remove_dir_all(&root).unwrap();
+
+        // Object, depfile, executable and state; the missing DLL is skipped, and so are the files outside.
+        assert_eq!(removed, 4);
+        assert!(src_dir_removed);
+        assert!(notes_kept);
+        assert!(outside_kept);
+    }
+
+    #[test]
+    fn recorded_output_dirs_are_found_for_every_profile_and_target() {
+        let root = temp_dir("recorded_dirs");
+        let dirs = [
+            root.join("output/debug/x64/libs/core"),
+            root.join("output/old-profile/windows-arm64/libs/core"),
+            root.join("output/release/x86/libs"),
+        ];
+        for dir in &dirs {
+            create_dir_all(dir).unwrap();
+            BuildState::default().save(dir).unwrap();
+        }
+        // No state, so nothing to clean there
+        create_dir_all(root.join("output/release/x64/libs/core")).unwrap();
+
+        let core = recorded_output_dirs(&root, Path::new("libs/core"));
+        let libs = recorded_output_dirs(&root, Path::new("libs"));
+        let none = recorded_output_dirs(&root, Path::new("app"));
+        remove_dir_all(&root).unwrap();
+
+        assert_eq!(core, [dirs[0].clone(), dirs[1].clone()]);
+        assert_eq!(libs, [dirs[2].clone()]);
+        assert!(none.is_empty());
+    }
+}
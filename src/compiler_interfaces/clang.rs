@@ -0,0 +1,149 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::compiler_interfaces::common::{CompileCommand, Compiler};
+use crate::compiler_interfaces::gcc::GccCompiler;
+use crate::compiler_interfaces::toolchain::{find_in_path, Toolchain};
+use crate::helpers::job_pool::JobPool;
+use crate::solution::{BuildProfile, Project, Solution, TargetArch, TargetPlatform};
+
+///
+/// Backend for the clang/clang++ drivers.
+/// Clang understands GCC's command line, so the work is delegated to a `GccCompiler` pointed at clang.
+/// The output layout is the same as with GCC (MinGW-w64's when targeting Windows).
+///
+pub struct ClangCompiler {
+    driver: GccCompiler,
+}
+
+impl ClangCompiler {
+    ///
+    /// Creates a Clang backend.
+    /// # Arguments
+    /// * `toolchain` - The clang, clang++ and archiver to use.
+    /// * `platform` - The platform to build for.
+    /// * `arch` - The architecture to build for.
+    /// * `use_lld` - Whether to link with LLVM's `lld` instead of the system linker.
+    /// * `profile` - The build profile.
+    /// * `job_pool` - The job pool shared by the build.
+    ///
+    pub fn new(toolchain: Toolchain, platform: TargetPlatform, arch: TargetArch, use_lld: bool, profile: BuildProfile, job_pool: Arc<JobPool>) -> Self {
+        ClangCompiler {
+            driver: GccCompiler {
+                gcc_path: toolchain.cc,
+                gcc_args: toolchain.cc_args,
+                gpp_path: toolchain.cxx,
+                gpp_args: toolchain.cxx_args,
+                ar_path: toolchain.ar,
+                linker: use_lld.then(|| "lld".to_string()),
+                platform,
+                arch,
+                target_flags: toolchain.target_flags,
+                profile,
+                job_pool,
+            },
+        }
+    }
+}
+
+impl Compiler for ClangCompiler {
+    fn compile_file(&self, project: &Project, abs_infile_path: &Path, abs_output_path: &Path, additional_includes: &[PathBuf], verbose: bool) -> Result<(), &'static str> {
+        self.driver.compile_file(project, abs_infile_path, abs_output_path, additional_includes, verbose)
+    }
+
+    fn compile_project(&self, project: &Project, solution: &Solution, solution_root: &Path, additional_include_directories: Vec<PathBuf>, verbose: bool) -> Result<(), &'static str> {
+        self.driver.compile_project(project, solution, solution_root, additional_include_directories, verbose)
+    }
+
+    fn compile_commands(&self, project: &Project, solution_root: &Path, additional_include_directories: &[PathBuf]) -> Result<Vec<CompileCommand>, &'static str> {
+        self.driver.compile_commands(project, solution_root, additional_include_directories)
+    }
+
+    fn link_project(&self, project: &Project, solution: &Solution, solution_root: &Path, dependencies: &[Project], verbose: bool) -> Result<(), &'static str> {
+        self.driver.link_project(project, solution, solution_root, dependencies, verbose)
+    }
+
+    fn detect_compiler_path() -> Option<String> {
+        find_in_path("clang").map(|p| p.to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::ClangCompiler;
+    use crate::compiler_interfaces::toolchain::Toolchain;
+    use crate::helpers::job_pool::JobPool;
+    use crate::solution::{BuildProfile, TargetArch, TargetPlatform};
+
+    fn compiler(cc: &str, cxx: &str, use_lld: bool) -> ClangCompiler {
+        let toolchain = Toolchain {
+            cc: cc.to_string(),
+            cc_args: Vec::new(),
+            cxx: cxx.to_string(),
+            cxx_args: Vec::new(),
+            ar: "llvm-ar".to_string(),
+            target_flags: vec!["--target=aarch64-linux-gnu".to_string()],
+        };
+        ClangCompiler::new(toolchain, TargetPlatform::Linux, TargetArch::ARM64, use_lld, BuildProfile::builtin("debug").unwrap(), Arc::new(JobPool::new(1, false)))
+    }
+
+    #[test]
+    fn sources_compile_with_the_driver_of_their_language_for_the_target() {
+        use std::fs::{create_dir_all, remove_dir_all, write};
+        use crate::compiler_interfaces::common::Compiler;
+        use crate::solution::ProjectType;
+        use crate::test_fixtures::{project, temp_dir};
+
+        let root = temp_dir("clang_commands");
+        create_dir_all(root.join("app")).unwrap();
+        write(root.join("app/main.cpp"), "int main() { return 0; }\n").unwrap();
+        write(root.join("app/util.c"), "int util(void) { return 0; }\n").unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let app = project("app", ProjectType::Executable, &[]);
+        let commands = compiler("clang", "clang++", false).compile_commands(&app, &root, &[]).unwrap();
+        remove_dir_all(&root).unwrap();
+
+        let mut drivers: Vec<(String, &str)> = commands
+            .iter()
+            .map(|c| (c.output.strip_prefix(&root).unwrap().display().to_string(), c.arguments[0].as_str()))
+            .collect();
+        drivers.sort();
+        assert_eq!(drivers, [("output/debug/arm64/app/main.cpp.o".to_string(), "clang++"), ("output/debug/arm64/app/util.c.o".to_string(), "clang")]);
+        assert!(commands.iter().all(|c| c.arguments.contains(&"--target=aarch64-linux-gnu".to_string())));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_with_lld_when_asked_to() {
+        use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+        use std::time::Duration;
+        use crate::compiler_interfaces::common::{project_output_dir, Compiler};
+        use crate::solution::ProjectType;
+        use crate::test_fixtures::{project, solution, stand_in_driver, temp_dir};
+
+        let root = temp_dir("clang_lld");
+        let clang = stand_in_driver(&root, "clang", Duration::ZERO);
+        let clangxx = stand_in_driver(&root, "clang++", Duration::ZERO);
+        create_dir_all(root.join("app")).unwrap();
+        write(root.join("app/main.c"), "int main(void) { return 0; }\n").unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let app = project("app", ProjectType::Executable, &[]);
+        let sol = solution(Vec::new());
+        let output_dir = project_output_dir(&root, &app, "debug", TargetPlatform::Linux, TargetArch::ARM64);
+        let mut link_commands = Vec::new();
+        for use_lld in [true, false] {
+            let compiler = compiler(clang.to_str().unwrap(), clangxx.to_str().unwrap(), use_lld);
+            compiler.compile_project(&app, &sol, &root, Vec::new(), false).unwrap();
+            compiler.link_project(&app, &sol, &root, &[], false).unwrap();
+            link_commands.push(read_to_string(output_dir.join("app")).unwrap());
+        }
+        remove_dir_all(&root).unwrap();
+
+        assert!(link_commands[0].contains("--target=aarch64-linux-gnu") && link_commands[0].contains("-fuse-ld=lld"));
+        assert!(!link_commands[1].contains("-fuse-ld"));
+    }
+}
@@ -1,6 +1,101 @@
+use std::collections::HashMap;
+use std::fs::{copy, create_dir_all, exists, remove_file, write};
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
 
-use crate::solution::{Project, Solution};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::build_state::{command_line, hash_file, hash_headers, BuildState, CompileRecord};
+use crate::helpers::console::Console;
+use crate::helpers::file_tools::{absolute_path, is_within, list_files};
+use crate::helpers::job_pool::JobPool;
+use crate::solution::{Project, Solution, TargetArch, TargetPlatform};
+
+/// The compiler backends spbuild can build with.
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CompilerKind {
+    Gcc,
+    Clang,
+    Msvc,
+}
+
+///
+/// Directory where the objects and artifacts of `project` built with `profile` for `platform` and `arch` are written:
+/// `<solution_root>/output/<profile>/<arch>/<project.path>` for the host platform,
+/// `<solution_root>/output/<profile>/<platform>-<arch>/<project.path>` when cross compiling for another platform.
+///
+pub fn project_output_dir(solution_root: &Path, project: &Project, profile: &str, platform: TargetPlatform, arch: TargetArch) -> PathBuf {
+    let target = if TargetPlatform::host() == Some(platform) {
+        arch.name().to_string()
+    } else {
+        format!("{}-{}", platform.name(), arch.name())
+    };
+    solution_root.join("output").join(profile).join(target).join(&project.path)
+}
+
+///
+/// Copies `dlls` next to an executable being linked in `abs_output_dir`, since Windows looks DLLs up there.
+/// DLLs that are already there and unchanged are left alone.
+/// Returns the paths of the copies, so they can be recorded in the build state.
+///
+pub fn copy_runtime_dlls(dlls: &[PathBuf], abs_output_dir: &Path, verbose: bool) -> Result<Vec<PathBuf>, &'static str> {
+    let mut copies = Vec::new();
+    for dll in dlls {
+        let Some(file_name) = dll.file_name() else { continue };
+        let destination = abs_output_dir.join(file_name);
+
+        if hash_file(dll) != hash_file(&destination) {
+            Console::log_verbose(&format!("Copying {} to {}", dll.display(), destination.display()), verbose);
+            copy(dll, &destination).map_err(|_| "Failed to copy DLL next to the executable")?;
+        }
+        copies.push(destination);
+    }
+    Ok(copies)
+}
+
+/// Name of the compilation database written at the solution root for editors and clang tooling.
+pub const COMPILE_COMMANDS_FILE: &str = "compile_commands.json";
+
+/// One entry of `compile_commands.json`: how a single source file is compiled.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CompileCommand {
+    /// Working directory of the compiler.
+    pub directory: PathBuf,
+    pub file: PathBuf,
+    /// The compiler followed by its arguments, exactly as spbuild runs them.
+    pub arguments: Vec<String>,
+    pub output: PathBuf,
+}
+
+impl CompileCommand {
+    /// Describes `command`, which compiles `file` into `output`.
+    pub fn new(command: &Command, file: PathBuf, output: PathBuf) -> CompileCommand {
+        let directory = command
+            .get_current_dir()
+            .or(output.parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        CompileCommand { directory, file, arguments: command_line(command), output }
+    }
+}
+
+///
+/// Writes `commands` to `<solution_root>/compile_commands.json`, replacing the previous one.
+/// Returns the path of the file written.
+///
+pub fn write_compile_commands(solution_root: &Path, commands: &[CompileCommand]) -> Result<PathBuf, &'static str> {
+    let path = solution_root.join(COMPILE_COMMANDS_FILE);
+    let json = serde_json::to_string_pretty(commands).map_err(|_| "Failed to serialize the compile commands")?;
+    write(&path, json + "\n").map_err(|_| "Failed to write compile_commands.json")?;
+    Ok(path)
+}
+
+/// One compiler backend per architecture being built.
+pub type Compilers = HashMap<TargetArch, Box<dyn Compiler + Sync>>;
 
 // TRAITS DEFINITIONS
 pub trait Compiler {
@@ -8,6 +103,7 @@ pub trait Compiler {
     /// Compiles a single source file.
     ///
     /// # Arguments
+    /// * `project` - The project the source file belongs to.
     /// * `abs_infile_path` - Absolute path to the input source file.
     /// * `abs_output_path` - Absolute path to the output file.
     /// * `additional_includes` - Additional include directories.
@@ -15,7 +111,7 @@ pub trait Compiler {
     /// # Returns
     /// * `Ok(())` if compilation is successful, otherwise an error message.
     ///
-    fn compile_file(&self, abs_infile_path: &PathBuf, abs_output_path: &PathBuf, additional_includes: &Vec<PathBuf>, verbose:bool) -> Result<(), &'static str>;
+    fn compile_file(&self, project: &Project, abs_infile_path: &Path, abs_output_path: &Path, additional_includes: &[PathBuf], verbose:bool) -> Result<(), &'static str>;
 
     ///
     /// Compiles an entire project.
@@ -31,25 +127,39 @@ pub trait Compiler {
     /// # Note:
     /// This function checks for any circular dependencies in the project before proceeding with compilation.
     ///
-    fn compile_project(&self, project: &Project, solution: &Solution, solution_root: &PathBuf, additional_include_directories: Vec<PathBuf>, verbose:bool) -> Result<(), &'static str>;
+    fn compile_project(&self, project: &Project, solution: &Solution, solution_root: &Path, additional_include_directories: Vec<PathBuf>, verbose:bool) -> Result<(), &'static str>;
+
+    ///
+    /// Crafts the command `compile_project` would run for each source file of a project, without running them.
+    /// Nothing is created, not even the output directory.
+    ///
+    /// # Arguments
+    /// * `project` - The project whose sources are listed.
+    /// * `solution_root` - The root path of the solution.
+    /// * `additional_include_directories` - Additional include directories, as given to `compile_project`.
+    /// # Returns
+    /// * One compile command per source file, otherwise an error message.
+    ///
+    fn compile_commands(&self, project: &Project, solution_root: &Path, additional_include_directories: &[PathBuf]) -> Result<Vec<CompileCommand>, &'static str>;
 
     ///
     /// Links the compiled object files of a project into a final executable or library.
-    /// Right now, it only links together object files into an executable : libraries are still kept as object files.
+    /// Static libraries are archived instead of linked.
     ///
     /// # Arguments
     /// * `project` - The project to link.
     /// * `solution` - The solution containing the project.
-    /// * `project_path` - The path to the project.
-    /// * `includes_paths` - Include paths for linking.
+    /// * `solution_root` - The root path of the solution.
+    /// * `dependencies` - The project's local dependencies, transitive and ordered so dependencies appear before dependents.
     /// * `verbose` - Whether to enable verbose output.
     /// # Returns
     /// * `Ok(())` if linking is successful, otherwise an error message.
     ///
-    fn link_project(&self, project: &Project, solution: &Solution, project_path: &PathBuf, includes_paths: Vec<PathBuf>, verbose: bool)  -> Result<(), &'static str>;
-    fn detect_compiler_path() -> Option<String>;
+    fn link_project(&self, project: &Project, solution: &Solution, solution_root: &Path, dependencies: &[Project], verbose: bool)  -> Result<(), &'static str>;
+    fn detect_compiler_path() -> Option<String> where Self: Sized;
 
-    fn build_root_from_config_path(project_path: &str) -> Result<PathBuf, &'static str> {
+    #[allow(dead_code)]
+    fn build_root_from_config_path(project_path: &str) -> Result<PathBuf, &'static str> where Self: Sized {
         // `project_path` is the path passed from CLI (currently the config file path).
         // Canonicalize so output paths are absolute and independent of current_dir.
         let cfg = Path::new(project_path)
@@ -61,3 +171,284 @@ pub trait Compiler {
             .map(|p| p.to_path_buf())
     }
 }
+
+///
+/// The parts of compiling sources that differ between backends.
+/// `compile_sources`, `compile_source` and `source_compile_commands` do the rest once for every backend:
+/// listing the sources, the incremental build state, scheduling on the job pool and running the compiler.
+///
+pub trait SourceCompiler: Sync {
+    /// Extension added to a source's name to name its object (`o` makes `main.cpp.o`).
+    const OBJECT_EXTENSION: &'static str;
+
+    /// The program compiling the sources, shown when a project starts compiling.
+    fn compiler_path(&self) -> &str;
+    /// Architecture the sources are compiled for.
+    fn arch(&self) -> TargetArch;
+    /// The job pool running the compilations.
+    fn job_pool(&self) -> &JobPool;
+    /// Directory receiving the objects of `project` (see `project_output_dir`).
+    fn output_dir(&self, abs_solution_root: &Path, project: &Project) -> PathBuf;
+
+    ///
+    /// Crafts the command compiling `abs_infile_path` into `abs_output_path`.
+    /// The command is not executed, so it can be compared against the one recorded in the build state.
+    ///
+    fn compile_command(&self, project: &Project, abs_infile_path: &Path, abs_output_path: &Path, additional_includes: &[PathBuf]) -> Command;
+
+    ///
+    /// Handles what the compiler printed once it exited, and returns what to show on the standard output.
+    /// The compiler's output is shown as is by default.
+    ///
+    fn filter_output(&self, _abs_infile_path: &Path, _abs_output_path: &Path, output: &Output) -> Result<Vec<u8>, &'static str> {
+        Ok(output.stdout.clone())
+    }
+
+    /// Returns true if the compiler knows how to compile `path` (C or C++ source).
+    fn is_source_file(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| ["c", "cpp", "cc", "cxx"].iter().any(|x| e.eq_ignore_ascii_case(x)))
+    }
+
+    ///
+    /// Returns the object file produced in `abs_output_dir` for the source at `rel_source_path` in the project's
+    /// source directory. Subdirectories are mirrored, so `core/core.c` and `core/sub/core.c` get distinct objects
+    /// (`core/core.c.o` and `core/sub/core.c.o`).
+    ///
+    fn object_path(&self, rel_source_path: &Path, abs_output_dir: &Path) -> PathBuf {
+        abs_output_dir.join(rel_source_path).with_added_extension(Self::OBJECT_EXTENSION)
+    }
+
+    /// Returns the dependency file listing the headers of `abs_output_path` (e.g. `main.cpp.o` -> `main.cpp.d`).
+    fn depfile_path(&self, abs_output_path: &Path) -> PathBuf {
+        abs_output_path.with_extension("d")
+    }
+}
+
+// Every source file of `project`, as its path relative to the project's source directory and its absolute path.
+fn project_sources<B: SourceCompiler>(backend: &B, project: &Project, solution_root: &Path) -> Result<Vec<(PathBuf, PathBuf)>, &'static str> {
+    let source_dir = solution_root
+        .join(&project.path)
+        .canonicalize()
+        .map_err(|e| {
+            Console::log_error(&format!("Project source directory not found: {} - {}", project.path.display(), e));
+            "Project source directory not found"
+        })?;
+
+    let mut sources = Vec::new();
+    for source_file in list_files(&source_dir).map_err(|_| "Failed to list source files")? {
+        let abs_source_file = source_dir
+            .join(&source_file)
+            .canonicalize()
+            .map_err(|_| "Failed to canonicalize path. The file likely doesn't exist")?;
+
+        if backend.is_source_file(&abs_source_file) {
+            sources.push((source_file, abs_source_file));
+        }
+    }
+    Ok(sources)
+}
+
+// Deletes an object that is no longer recorded, and its depfile. Files outside of `abs_output_dir` are left alone.
+fn remove_object<B: SourceCompiler>(backend: &B, object: &Path, abs_output_dir: &Path, verbose: bool) {
+    for file in [object.to_path_buf(), backend.depfile_path(object)] {
+        if file.exists() && is_within(&file, abs_output_dir) {
+            Console::log_verbose(format!("Removing stale {}", file.display()).as_str(), verbose);
+            let _ = remove_file(&file);
+        }
+    }
+}
+
+///
+/// Compiles a single source file with `backend` (see `Compiler::compile_file`).
+///
+pub fn compile_source<B: SourceCompiler>(
+    backend: &B,
+    project: &Project,
+    abs_infile_path: &Path,
+    abs_output_path: &Path,
+    additional_includes: &[PathBuf],
+    verbose: bool,
+) -> Result<(), &'static str> {
+    if !backend.is_source_file(abs_infile_path) {
+        Console::log_warning(format!("Unsupported source file extension: {}", &abs_infile_path.display()).as_str());
+        return Ok(());
+    }
+
+    if let Some(parent) = abs_output_path.parent() {
+        create_dir_all(parent).map_err(|_| "Failed to create output subdirectory")?;
+    }
+
+    Console::log_verbose(&format!("input:  {}", abs_infile_path.display()), verbose);
+    Console::log_verbose(&format!("output: {}", abs_output_path.display()), verbose);
+
+    let output = backend
+        .compile_command(project, abs_infile_path, abs_output_path, additional_includes)
+        .output()
+        .map_err(|_| "Failed to execute the compiler")?;
+    let messages = backend.filter_output(abs_infile_path, abs_output_path, &output)?;
+
+    // Other files may be compiling in parallel: hold both streams so this file's output stays in one piece.
+    let mut stdout = io::stdout().lock();
+    let mut stderr = io::stderr().lock();
+
+    Console::log_info(&format!("status: {}", output.status));
+
+    stdout.write_all(&messages).map_err(|_| "Failed to write to stdout")?;
+    stderr.write_all(&output.stderr).map_err(|_| "Failed to write to stderr")?;
+
+    if output.status.success() {
+        Console::log_info(format!(">> {} compiled successfully!", abs_infile_path.display()).as_str());
+        Ok(())
+    } else {
+        Err("Compilation failed.")
+    }
+}
+
+///
+/// Compiles the sources of `project` with `backend` (see `Compiler::compile_project`).
+/// Sources whose contents, headers and command did not change since the last build are skipped;
+/// the others compile concurrently on the backend's job pool.
+///
+pub fn compile_sources<B: SourceCompiler>(
+    backend: &B,
+    project: &Project,
+    solution_root: &Path,
+    include_directories: &[PathBuf],
+    verbose: bool,
+) -> Result<(), &'static str> {
+    let abs_solution_root = solution_root.canonicalize().map_err(|_| "Failed to canonicalize solution root path")?;
+
+    Console::log_info(&format!(
+        "Compiling Project: {} version {} ({}) for {} using {}\n",
+        project.name,
+        project.version,
+        project.path.display(),
+        backend.arch().name(),
+        backend.compiler_path()
+    ));
+
+    let sources = project_sources(backend, project, solution_root)?;
+
+    let rel_output_dir = &backend.output_dir(&abs_solution_root, project);
+
+    if !exists(rel_output_dir).unwrap_or(false) {
+        // Creates output directory if it doesn't exist
+        Console::log_verbose(&format!("Project output directory not found: {}", project.path.display()), verbose);
+        create_dir_all(rel_output_dir).map_err(|_| "Failed to create output directory")?;
+    };
+
+    let abs_output_dir = rel_output_dir.canonicalize().map_err(|_| "Failed to canonicalize output directory")?;
+
+    // Incremental build: sources whose contents and command did not change are skipped.
+    let mut state = BuildState::load(&abs_output_dir);
+    let mut up_to_date_count = 0;
+    let mut outdated: Vec<(PathBuf, CompileRecord)> = Vec::new();
+    let mut current_sources: Vec<PathBuf> = Vec::new();
+
+    for (rel_source_file, abs_source_file) in sources {
+        current_sources.push(abs_source_file.clone());
+        let abs_object_file = backend.object_path(&rel_source_file, &abs_output_dir);
+        let command = command_line(&backend.compile_command(project, &abs_source_file, &abs_object_file, include_directories));
+        let source_hash = hash_file(&abs_source_file).ok_or("Failed to read source file")?;
+
+        if state.is_compile_up_to_date(&abs_source_file, source_hash, &command) {
+            Console::log_verbose(&format!("Up to date: {}", rel_source_file.display()), verbose);
+            up_to_date_count += 1;
+            continue;
+        }
+
+        outdated.push((abs_source_file, CompileRecord {
+            source_hash,
+            command,
+            object: abs_object_file,
+            headers: Default::default(),
+        }));
+    }
+
+    if up_to_date_count > 0 {
+        Console::log_info(&format!("{} source file(s) already up to date", up_to_date_count));
+    }
+
+    // Sources that were deleted must not end up in the link anymore, nor leave their objects behind.
+    let (current, deleted) = std::mem::take(&mut state.compiled)
+        .into_iter()
+        .partition(|(source, _)| current_sources.contains(source));
+    state.compiled = current;
+    for record in deleted.values() {
+        remove_object(backend, &record.object, &abs_output_dir, verbose);
+    }
+
+    // Translation units are independent: compile them concurrently.
+    let results = backend.job_pool().run(&outdated, |(abs_source_file, record)| {
+        Console::log_info(&format!("Compiling source file: {}", abs_source_file.display()));
+        compile_source(backend, project, abs_source_file, &record.object, include_directories, verbose)
+    });
+
+    let mut failed_count = 0;
+    let mut skipped_count = 0;
+    for ((abs_source_file, mut record), result) in outdated.into_iter().zip(results) {
+        match result {
+            Some(Ok(())) => {
+                record.headers = hash_headers(&backend.depfile_path(&record.object), &abs_source_file);
+                let object = record.object.clone();
+                if let Some(previous) = state.compiled.insert(abs_source_file, record)
+                    && previous.object != object {
+                    remove_object(backend, &previous.object, &abs_output_dir, verbose);
+                }
+            }
+            Some(Err(_)) => {
+                // Forget the failed source and keep what did compile so it isn't rebuilt next time.
+                if let Some(previous) = state.compiled.remove(&abs_source_file) {
+                    remove_object(backend, &previous.object, &abs_output_dir, verbose);
+                }
+                remove_object(backend, &record.object, &abs_output_dir, verbose);
+                failed_count += 1;
+            }
+            None => skipped_count += 1,
+        }
+    }
+
+    state.save(&abs_output_dir)?;
+
+    if failed_count > 0 {
+        if skipped_count > 0 {
+            Console::log_warning(&format!("{} source file(s) were not compiled because of previous errors", skipped_count));
+        }
+        Console::log_error(&format!("{} source file(s) failed to compile in project {}", failed_count, project.name));
+        return Err("Compilation failed.");
+    }
+
+    if skipped_count > 0 {
+        // Another project failed while this one was compiling.
+        return Err("Compilation stopped because of previous errors.");
+    }
+
+    Ok(())
+}
+
+///
+/// Crafts the command `compile_sources` would run for each source of `project`, without running them
+/// (see `Compiler::compile_commands`).
+///
+pub fn source_compile_commands<B: SourceCompiler>(
+    backend: &B,
+    project: &Project,
+    solution_root: &Path,
+    include_directories: &[PathBuf],
+) -> Result<Vec<CompileCommand>, &'static str> {
+    let abs_solution_root = solution_root.canonicalize().map_err(|_| "Failed to canonicalize solution root path")?;
+
+    // Same paths as compile_sources, but the output directory is not created.
+    let abs_output_dir = absolute_path(&backend.output_dir(&abs_solution_root, project));
+
+    let mut commands = Vec::new();
+    for (rel_source_file, abs_source_file) in project_sources(backend, project, solution_root)? {
+        let abs_object_file = backend.object_path(&rel_source_file, &abs_output_dir);
+        let command = backend.compile_command(project, &abs_source_file, &abs_object_file, include_directories);
+        commands.push(CompileCommand::new(&command, abs_source_file, abs_object_file));
+    }
+
+    Ok(commands)
+}
@@ -1,230 +1,384 @@
-use std::fs::{exists, create_dir_all};
+use std::fs::remove_file;
 use std::path::{Path, PathBuf};
 
 use std::{io};
 use std::io::Write;
 
 use std::process::Command;
-use crate::compiler_interfaces::common::Compiler;
+use std::sync::Arc;
+use crate::build_state::{command_line, hash_files, BuildState, LinkRecord};
+use crate::compiler_interfaces::common::{compile_source, compile_sources, copy_runtime_dlls, project_output_dir, source_compile_commands, CompileCommand, Compiler, SourceCompiler};
+use crate::compiler_interfaces::toolchain::find_in_path;
+use crate::dependency_manager::local_resolve::{link_dependencies, resolve_link_libraries};
 use crate::helpers::console::Console;
 use crate::helpers::file_tools::*;
-use crate::solution::{Project, ProjectType, Solution};
+use crate::helpers::job_pool::JobPool;
+use crate::solution::{BuildProfile, CStandard, CxxStandard, Optimization, WarningLevel, Project, ProjectType, Solution, TargetArch, TargetPlatform};
 
 
+///
+/// Backend for GCC and compatible drivers (the Clang and MinGW-w64 backends reuse it).
+///
 pub struct GccCompiler {
+    /// C compiler driver.
     pub gcc_path: String,
+    /// Arguments given to `gcc_path` before any other (`gcc` when it is `ccache`).
+    pub gcc_args: Vec<String>,
+    /// C++ compiler driver, also used to link.
     pub gpp_path: String,
+    /// Arguments given to `gpp_path` before any other.
+    pub gpp_args: Vec<String>,
+    /// Archiver used to create static libraries.
+    pub ar_path: String,
+    /// Linker selected with `-fuse-ld=` (e.g. `lld`). `None` keeps the driver's default.
+    pub linker: Option<String>,
+    /// Platform this compiler builds for. Selects the output directory and the artifact naming conventions.
+    pub platform: TargetPlatform,
+    /// Architecture this compiler builds for. Selects the output directory.
+    pub arch: TargetArch,
+    /// Flags selecting the architecture, passed to every compile and link command.
+    pub target_flags: Vec<String>,
+    /// Build profile: optimisation, debug info, defines and extra flags. Selects the output directory.
+    pub profile: BuildProfile,
+    pub job_pool: Arc<JobPool>,
 }
 
 impl GccCompiler {
-    pub fn detect_gpp_path() -> Option<String> {
-        let gcc_path = Path::new("/usr/bin/g++");
+    ///
+    /// Returns the final artifact of `project` in `abs_output_dir`:
+    /// `lib<name>.a` for static libraries, `lib<name>.so.<version>` for shared libraries and `<name>` for executables.
+    /// When building for Windows, shared libraries are `<name>.dll` and executables `<name>.exe`.
+    ///
+    pub fn artifact_path(&self, project: &Project, abs_output_dir: &Path) -> PathBuf {
+        let windows = self.platform == TargetPlatform::Windows;
+        match project.project_type {
+            ProjectType::StaticLib => abs_output_dir.join(format!("lib{}.a", project.name)),
+            ProjectType::DynamicLib if windows => abs_output_dir.join(format!("{}.dll", project.name)),
+            ProjectType::DynamicLib => abs_output_dir.join(GccCompiler::shared_library_names(project).0),
+            ProjectType::Executable if windows => abs_output_dir.join(format!("{}.exe", project.name)),
+            ProjectType::Executable => abs_output_dir.join(&project.name),
+        }
+    }
 
-        if exists(gcc_path).expect("G++ path check failed") {
-            gcc_path.to_str().map(|s| s.to_string())
-        } else {
-            None
+    /// Returns the import library written next to a Windows DLL (`foo.dll` -> `libfoo.dll.a`). Dependents link against it.
+    pub fn import_library_path(project: &Project, abs_output_dir: &Path) -> PathBuf {
+        abs_output_dir.join(format!("lib{}.dll.a", project.name))
+    }
+
+    ///
+    /// Returns the names of a shared library: the real file, its soname and the name used by the linker.
+    /// For `foo` version `1.2.3`: `libfoo.so.1.2.3`, `libfoo.so.1` and `libfoo.so`.
+    /// Without a version, the three names are `libfoo.so`.
+    ///
+    pub fn shared_library_names(project: &Project) -> (String, String, String) {
+        let linker_name = format!("lib{}.so", project.name);
+        let version = project.version.trim();
+        let major = version.split('.').next().unwrap_or("");
+
+        if version.is_empty() || major.is_empty() {
+            return (linker_name.clone(), linker_name.clone(), linker_name);
         }
+
+        (
+            format!("{}.{}", linker_name, version),
+            format!("{}.{}", linker_name, major),
+            linker_name,
+        )
     }
 }
 
-impl Compiler for GccCompiler {
-    fn compile_file(
-        &self,
-        abs_infile_path: &PathBuf,
-        abs_output_dir: &PathBuf,
-        additional_includes: &Vec<PathBuf>,
-        _verbose: bool,
-    ) -> Result<(), &'static str> {
+impl SourceCompiler for GccCompiler {
+    const OBJECT_EXTENSION: &'static str = "o";
 
-        // Checks for edge cases I don't even know if they can happen
-        let last_component_abs_output_dir = abs_infile_path.components().last();
-        if last_component_abs_output_dir.is_none() {
-            return Err("Invalid input file path");
-        }
+    fn compiler_path(&self) -> &str {
+        &self.gcc_path
+    }
 
-        let abs_output_path = abs_output_dir.join(last_component_abs_output_dir.unwrap()).with_added_extension("o");
+    fn arch(&self) -> TargetArch {
+        self.arch
+    }
 
-        // Check extension
-        if abs_infile_path.extension() != Some(std::ffi::OsStr::new("c")) &&
-           abs_infile_path.extension() != Some(std::ffi::OsStr::new("cpp")) &&
-           abs_infile_path.extension() != Some(std::ffi::OsStr::new("cc")) &&
-           abs_infile_path.extension() != Some(std::ffi::OsStr::new("cxx")) {
-            Console::log_warning(format!("Unsupported source file extension for GCC compiler: {}", &abs_infile_path.display()).as_str());
-            return Ok(());
-        }
+    fn job_pool(&self) -> &JobPool {
+        &self.job_pool
+    }
 
-        if let Some(parent) = abs_output_path.parent() {
-            create_dir_all(parent).map_err(|_| "Failed to create output subdirectory")?;
-        }
+    fn output_dir(&self, abs_solution_root: &Path, project: &Project) -> PathBuf {
+        project_output_dir(abs_solution_root, project, &self.profile.name, self.platform, self.arch)
+    }
 
-        // Use g++ for C++ sources so the preprocessor selects the right language.
-        let driver = if abs_infile_path
+    fn compile_command(&self, project: &Project, abs_infile_path: &Path, abs_output_path: &Path, additional_includes: &[PathBuf]) -> Command {
+        let cpp = abs_infile_path
             .extension()
             .and_then(|e| e.to_str())
-            .is_some_and(|e| e.eq_ignore_ascii_case("cpp") || e.eq_ignore_ascii_case("cc") || e.eq_ignore_ascii_case("cxx"))
-        {
-            "/usr/bin/g++"
-        } else {
-            &self.gcc_path
-        };
+            .is_some_and(|e| e.eq_ignore_ascii_case("cpp") || e.eq_ignore_ascii_case("cc") || e.eq_ignore_ascii_case("cxx"));
 
-        Console::log_verbose(&format!("input:  {}", abs_infile_path.display()), _verbose);
-        Console::log_verbose(&format!("output: {}", abs_output_path.display()), _verbose);
+        // Use g++ for C++ sources so the preprocessor selects the right language.
+        let (driver, driver_args) = if cpp { (&self.gpp_path, &self.gpp_args) } else { (&self.gcc_path, &self.gcc_args) };
 
-        // Crafts the command
         let mut command = Command::new(driver);
+        if let Some(parent) = abs_output_path.parent() {
+            command.current_dir(parent);
+        }
         command
-            .current_dir(&abs_output_dir)
+            .args(driver_args)
+            .args(&self.target_flags)
             .arg("-c")
-            .arg(&abs_infile_path)
+            .arg(abs_infile_path)
             .arg("-o")
-            .arg(&abs_output_path);
-
-        for include_path in additional_includes {
-            // Include paths are expected to be absolute or already correctly rooted.
-            command.arg("-I").arg(include_path);
+            .arg(abs_output_path)
+            // Lists the included (non-system) headers so editing one of them triggers a rebuild.
+            .arg("-MMD")
+            .arg("-MF")
+            .arg(self.depfile_path(abs_output_path));
+
+        // Shared libraries need position independent code. Static libraries get it too so they can be
+        // linked into a shared library. Windows code is always position independent.
+        if project.project_type != ProjectType::Executable && self.platform != TargetPlatform::Windows {
+            command.arg("-fPIC");
         }
 
-        // Executes the command
-        let output = command
-            .output()
-            .map_err(|_| "Failed to execute GCC")?;
-
-        Console::log_info(&format!("status: {}", output.status));
-
-        io::stdout().write_all(&output.stdout).map_err(|_| "Failed to write to stdout")?;
-        io::stderr().write_all(&output.stderr).map_err(|_| "Failed to write to stderr")?;
+        command.arg(match project.optimization(&self.profile) {
+            Optimization::None => "-O0",
+            Optimization::Speed => "-O2",
+            Optimization::Size => "-Os",
+        });
+        if self.profile.debug_info {
+            command.arg("-g");
+        }
 
-        if output.status.success() {
-            Console::log_info(&format!(">> {} compiled successfully!", abs_infile_path.display()).as_str());
-            Ok(())
+        let standard = if cpp {
+            project.cxx_standard.map(|s| match s {
+                CxxStandard::Cxx11 => "c++11",
+                CxxStandard::Cxx14 => "c++14",
+                CxxStandard::Cxx17 => "c++17",
+                CxxStandard::Cxx20 => "c++20",
+                CxxStandard::Cxx23 => "c++2b", // Spelling understood by older GCC and Clang too
+            })
         } else {
-            Err("Compilation failed.")
+            project.c_standard.map(|s| match s {
+                CStandard::C89 => "c89",
+                CStandard::C99 => "c99",
+                CStandard::C11 => "c11",
+                CStandard::C17 => "c17",
+                CStandard::C23 => "c2x",
+            })
+        };
+        if let Some(standard) = standard {
+            command.arg(format!("-std={}", standard));
         }
-    }
-
-
-    fn compile_project(
-        &self,
-        project: &Project,
-        solution: &Solution, // Will probably be used
-        solution_root: &PathBuf,
-        include_directories: Vec<PathBuf>,
-        _verbose: bool,
-    ) -> Result<(), &'static str> {
-
-        let gcc_path = GccCompiler::detect_compiler_path().ok_or("GCC compiler not found on system")?;
-        let abs_solution_root = solution_root.canonicalize().map_err(|_| "Failed to canonicalize solution root path")?;
-
-        Console::log_info(&format!(
-            "Compiling Project: {} version {} ({}) using GCC at {}\n",
-            project.name,
-            project.version,
-            project.path.display(),
-            &gcc_path
-        ));
-
-        let source_dir = solution_root
-            .join(&project.path)
-            .canonicalize()
-            .map_err(|e| {
-                Console::log_error(&format!("Project source directory not found: {} - {}", project.path.display(), e));
-                "Project source directory not found"
-            })?;
-
-        let files = list_files(&source_dir).map_err(|_| "Failed to list source files")?;
-
 
-        let rel_output_dir = &abs_solution_root
-            .join("output")
-            .join(&project.path);
-
-
-        if !exists(rel_output_dir).unwrap_or(false) {
-            // Creates output directory if it doesn't exist
-            Console::log_verbose(&format!("Project output directory not found: {}", project.path.display()), _verbose);
-            create_dir_all(rel_output_dir).map_err(|_| "Failed to create output directory")?;
-        };
+        match project.warning_level {
+            WarningLevel::None => { command.arg("-w"); }
+            WarningLevel::Default => {}
+            WarningLevel::All => { command.arg("-Wall"); }
+            WarningLevel::Extra => { command.arg("-Wall").arg("-Wextra"); }
+        }
+        if project.warnings_as_errors {
+            command.arg("-Werror");
+        }
 
-        let abs_output_dir = rel_output_dir.canonicalize().map_err(|_| "Failed to canonicalize output directory")?;
+        for define in self.profile.defines.iter().chain(&project.defines) {
+            command.arg(format!("-D{}", define));
+        }
 
+        for include_path in additional_includes {
+            // Include paths are expected to be absolute or already correctly rooted.
+            command.arg("-I").arg(include_path);
+        }
 
-        for source_file in files {
-            // list_files returns paths like ./main.c relative to source_dir
-            let rel = source_file.to_string_lossy().into_owned();
-            let project_path_str = &project.path.to_string_lossy().into_owned();
+        command.args(&self.profile.compile_flags);
+        command.args(&project.compile_flags);
 
-            let abs_source_file = solution_root
-                .join(project_path_str)
-                .join(&source_file)
-                .canonicalize()
-                .map_err(|_| "Failed to canonicalize path. The file likely doesn't exist")?;
+        command
+    }
+}
 
-            Console::log_info(&format!("Compiling source file: {}", &rel));
-            self.compile_file(&abs_source_file, &abs_output_dir, &include_directories, _verbose)?;
-        }
+impl Compiler for GccCompiler {
+    fn compile_file(&self, project: &Project, abs_infile_path: &Path, abs_output_path: &Path, additional_includes: &[PathBuf], verbose: bool) -> Result<(), &'static str> {
+        compile_source(self, project, abs_infile_path, abs_output_path, additional_includes, verbose)
+    }
 
-        Ok(())
+    fn compile_project(&self, project: &Project, _solution: &Solution, solution_root: &Path, include_directories: Vec<PathBuf>, verbose: bool) -> Result<(), &'static str> {
+        compile_sources(self, project, solution_root, &include_directories, verbose)
     }
 
+    fn compile_commands(&self, project: &Project, solution_root: &Path, include_directories: &[PathBuf]) -> Result<Vec<CompileCommand>, &'static str> {
+        source_compile_commands(self, project, solution_root, include_directories)
+    }
 
     fn link_project(
         &self, project: &Project,
-        solution: &Solution, // Will probably be used
-        solution_root: &PathBuf,
-        includes_paths: Vec<PathBuf>,
-        _verbose: bool) -> Result<(), &'static str> {
+        _solution: &Solution, // Will probably be used
+        solution_root: &Path,
+        dependencies: &[Project],
+        verbose: bool) -> Result<(), &'static str> {
 
-        if project.project_type == ProjectType::StaticLib
-        {
-            Console::log_info("Static library project detected; skipping linking step.");
-            return Ok(());
-        }
+        let abs_solution_root = solution_root.canonicalize().map_err(|_| "Failed to canonicalize solution root path")?;
 
         // Absolute path to the project's output directory containing object files.
-        let abs_project_output_path = &solution_root.join("output").join(&project.path).canonicalize().map_err(|_| {"Project Output Path not found"})?;
-        let files = list_files(&abs_project_output_path).map_err(|_| "Failed to list object files")?;
+        let abs_project_output_path = &project_output_dir(&abs_solution_root, project, &self.profile.name, self.platform, self.arch).canonicalize().map_err(|_| {"Project Output Path not found"})?;
+        let mut state = BuildState::load(abs_project_output_path);
 
-        // Project's object files
-        let mut object_files = find_object_files(&files, &abs_project_output_path);
+        // Project's object files, as recorded by compile_project
+        let object_files: Vec<PathBuf> = state.compiled.values().map(|r| r.object.clone()).collect();
 
         if object_files.is_empty() {
             Console::log_warning(format!("No object files were found in directory: {}. This may be unintended behavior", abs_project_output_path.display()).as_str());
             return Ok(());  // Nothing to link, but not an error.
         }
 
-        // Dependencies' object files
-        for path in includes_paths {
-            let files = list_files(&path).map_err(|_| "Failed to list dependency files")?;
-            let mut dep_object_files = find_object_files(&files, &path);
-            object_files.append(&mut dep_object_files);
+        // For project `alpha`, output executable is at `<project_root>/output/alpha/alpha`.
+        let output_artifact = self.artifact_path(project, abs_project_output_path);
+
+        let mut command;
+        let mut inputs = object_files.clone();
+
+        if project.project_type == ProjectType::StaticLib {
+            // `D` makes the archive deterministic, so dependents are only re-linked when its contents change.
+            command = Command::new(&self.ar_path);
+            command.current_dir(abs_project_output_path);
+            command.arg("rcsD").arg(&output_artifact);
+            for obj in &object_files {
+                command.arg(obj);
+            }
+        } else {
+            command = Command::new(&self.gpp_path);
+            command.current_dir(abs_project_output_path);
+            command.args(&self.gpp_args);
+            command.args(&self.target_flags);
+            command.args(&self.profile.link_flags);
+            command.args(&project.link_flags);
+            if let Some(linker) = &self.linker {
+                command.arg(format!("-fuse-ld={}", linker));
+            }
+            for obj in &object_files {
+                command.arg(obj);
+            }
+
+            if project.project_type == ProjectType::DynamicLib {
+                command.arg("-shared");
+                if self.platform == TargetPlatform::Windows {
+                    let import_library = GccCompiler::import_library_path(project, abs_project_output_path);
+                    command.arg(format!("-Wl,--out-implib,{}", import_library.display()));
+                } else {
+                    let (_, soname, _) = GccCompiler::shared_library_names(project);
+                    command.arg(format!("-Wl,-soname,{}", soname));
+                }
+            }
+
+            // Libraries must come after the objects using them, dependents before dependencies.
+            // Those behind a shared library were linked into it already.
+            for dep in link_dependencies(project, dependencies) {
+                let dep_output_dir = project_output_dir(&abs_solution_root, dep, &self.profile.name, self.platform, self.arch);
+                let dep_artifact = self.artifact_path(dep, &dep_output_dir);
+
+                match dep.project_type {
+                    ProjectType::StaticLib => {
+                        command.arg(&dep_artifact);
+                    }
+                    ProjectType::DynamicLib if self.platform == TargetPlatform::Windows => {
+                        // Windows has no rpath: the DLL is copied next to the executables using it instead.
+                        let import_library = GccCompiler::import_library_path(dep, &dep_output_dir);
+                        command.arg(&import_library);
+                        inputs.push(import_library);
+                        continue;
+                    }
+                    ProjectType::DynamicLib => {
+                        // Found at runtime relative to our own location, so binaries run straight from output/.
+                        let rpath = Path::new("$ORIGIN").join(relative_path(abs_project_output_path, &dep_output_dir));
+                        command
+                            .arg("-L").arg(&dep_output_dir)
+                            .arg(format!("-l{}", dep.name))
+                            .arg(format!("-Wl,-rpath,{}", rpath.display()));
+                    }
+                    ProjectType::Executable => continue,
+                }
+
+                inputs.push(dep_artifact);
+            }
+
+            // System and prebuilt libraries, ours and the ones our static libraries need.
+            let libraries = resolve_link_libraries(project, dependencies, &abs_solution_root);
+            for dir in &libraries.library_dirs {
+                command.arg("-L").arg(dir);
+            }
+            for lib in &libraries.prebuilt {
+                command.arg(lib);
+                let shared = lib.file_name().is_some_and(|n| {
+                    let n = n.to_string_lossy();
+                    n.ends_with(".so") || n.contains(".so.")
+                });
+                if shared && self.platform != TargetPlatform::Windows && let Some(dir) = lib.parent() {
+                    // Prebuilt shared libraries are loaded from where they are.
+                    command.arg(format!("-Wl,-rpath,{}", dir.display()));
+                }
+                inputs.push(lib.clone());
+            }
+            for name in &libraries.system {
+                command.arg(format!("-l{}", name));
+            }
+
+            command.arg("-o").arg(&output_artifact);
         }
 
+        // Only re-link when an input (ours or a dependency's) or the command changed.
+        let link_record = LinkRecord {
+            command: command_line(&command),
+            inputs: hash_files(&inputs)?,
+            output: output_artifact.clone(),
+        };
 
-        // For project `alpha`, output executable is at `<project_root>/output/alpha/alpha`.
-        let output_executable = abs_project_output_path.join(&project.name);
-        Console::log_info(&format!("Linking executable: {}", output_executable.display()));
+        if project.project_type == ProjectType::Executable && self.platform == TargetPlatform::Windows {
+            let dlls: Vec<PathBuf> = dependencies
+                .iter()
+                .filter(|d| d.project_type == ProjectType::DynamicLib)
+                .map(|d| self.artifact_path(d, &project_output_dir(&abs_solution_root, d, &self.profile.name, self.platform, self.arch)))
+                .collect();
+            state.produced.extend(copy_runtime_dlls(&dlls, abs_project_output_path, verbose)?);
+        }
 
-        let mut command = Command::new(&self.gpp_path);
-        command.current_dir(&abs_project_output_path);
-        for obj in &object_files {
-            command.arg(obj);
+        if state.is_link_up_to_date(&link_record) {
+            Console::log_info(&format!("Artifact is up to date: {}", output_artifact.display()));
+            return state.save(abs_project_output_path);
         }
 
-        command.arg("-o").arg(&output_executable);
+        // `ar` only adds or replaces members: start from scratch so deleted sources don't linger in the archive.
+        if project.project_type == ProjectType::StaticLib && output_artifact.exists() {
+            remove_file(&output_artifact).map_err(|_| "Failed to remove outdated static library")?;
+        }
 
-        Console::log_verbose(&format!("Linking command: {:?}", command), _verbose);
+        Console::log_info(&format!("Linking: {}", output_artifact.display()));
+        Console::log_verbose(&format!("Linking command: {:?}", command), verbose);
 
         let output = command
             .output()
-            .map_err(|_| "Failed to execute GCC for linking")?;
+            .map_err(|_| "Failed to execute the linker")?;
 
-        println!("status: {}", output.status);
+        Console::log_info(&format!("status: {}", output.status));
 
         io::stdout().write_all(&output.stdout).map_err(|_| "Failed to write to stdout")?;
         io::stderr().write_all(&output.stderr).map_err(|_| "Failed to write to stderr")?;
         if output.status.success() {
+            if project.project_type == ProjectType::DynamicLib && self.platform != TargetPlatform::Windows {
+                // libfoo.so -> libfoo.so.1 -> libfoo.so.1.2.3
+                let (real_name, soname, linker_name) = GccCompiler::shared_library_names(project);
+                if soname != real_name {
+                    replace_symlink(Path::new(&real_name), &abs_project_output_path.join(&soname))
+                        .map_err(|_| "Failed to create soname symlink")?;
+                    state.produced.insert(abs_project_output_path.join(&soname));
+                }
+                if linker_name != soname {
+                    replace_symlink(Path::new(&soname), &abs_project_output_path.join(&linker_name))
+                        .map_err(|_| "Failed to create linker name symlink")?;
+                    state.produced.insert(abs_project_output_path.join(&linker_name));
+                }
+            }
+            if project.project_type == ProjectType::DynamicLib && self.platform == TargetPlatform::Windows {
+                state.produced.insert(GccCompiler::import_library_path(project, abs_project_output_path));
+            }
+
+            state.link = Some(link_record);
+            state.save(abs_project_output_path)?;
             Console::log_success("Linked successfully.");
             Ok(())
         } else {
@@ -233,12 +387,271 @@ impl Compiler for GccCompiler {
     }
 
     fn detect_compiler_path() -> Option<String> {
-        let gcc_path = Path::new("/usr/bin/gcc");
+        find_in_path("gcc").map(|p| p.to_string_lossy().into_owned())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use super::GccCompiler;
+    use crate::build_state::BuildState;
+    use crate::compiler_interfaces::common::{project_output_dir, Compiler};
+    use crate::helpers::job_pool::JobPool;
+    use crate::solution::{BuildProfile, ProjectType, TargetArch, TargetPlatform};
+    use crate::test_fixtures::{project, solution, temp_dir};
+
+    fn compiler(gcc_path: &str, jobs: usize) -> GccCompiler {
+        GccCompiler {
+            gcc_path: gcc_path.to_string(),
+            gcc_args: Vec::new(),
+            gpp_path: gcc_path.to_string(),
+            gpp_args: Vec::new(),
+            ar_path: "ar".to_string(),
+            linker: None,
+            platform: TargetPlatform::Linux,
+            arch: TargetArch::X64,
+            target_flags: Vec::new(),
+            profile: BuildProfile::builtin("debug").unwrap(),
+            job_pool: Arc::new(JobPool::new(jobs, false)),
+        }
+    }
 
-        if exists(gcc_path).expect("GCC path check failed") {
-            gcc_path.to_str().map(|s| s.to_string())
-        } else {
-            None
+    #[test]
+    fn profiles_select_flags_and_output_directories() {
+        use std::path::Path;
+        use crate::build_state::command_line;
+        use crate::compiler_interfaces::common::SourceCompiler;
+
+        let lib = project("lib", ProjectType::StaticLib, &[]);
+        let mut debug = compiler("gcc", 1);
+        debug.gpp_path = "g++".to_string();
+        let release = GccCompiler { profile: BuildProfile::builtin("release").unwrap(), ..compiler("gcc", 1) };
+
+        let root = Path::new("/sol");
+        let debug_dir = debug.output_dir(root, &lib);
+        let release_dir = release.output_dir(root, &lib);
+        assert_eq!(debug_dir, Path::new("/sol/output/debug/x64/lib"));
+        assert_eq!(release_dir, Path::new("/sol/output/release/x64/lib"));
+
+        let command = debug.compile_command(&lib, Path::new("/sol/lib/a.cpp"), &debug_dir.join("a.cpp.o"), &[]);
+        assert_eq!(
+            command_line(&command),
+            ["g++", "-c", "/sol/lib/a.cpp", "-o", "/sol/output/debug/x64/lib/a.cpp.o", "-MMD", "-MF", "/sol/output/debug/x64/lib/a.cpp.d", "-fPIC", "-O0", "-g", "-DDEBUG"]
+        );
+        let command = release.compile_command(&lib, Path::new("/sol/lib/a.c"), &release_dir.join("a.c.o"), &[]);
+        assert_eq!(
+            command_line(&command),
+            ["gcc", "-c", "/sol/lib/a.c", "-o", "/sol/output/release/x64/lib/a.c.o", "-MMD", "-MF", "/sol/output/release/x64/lib/a.c.d", "-fPIC", "-O2", "-DNDEBUG"]
+        );
+    }
+
+    #[test]
+    fn project_settings_are_translated_after_the_profile() {
+        use std::path::Path;
+        use crate::build_state::command_line;
+        use crate::compiler_interfaces::common::SourceCompiler;
+        use crate::solution::{CStandard, CxxStandard, Optimization, WarningLevel};
+
+        let mut profile = BuildProfile::builtin("release").unwrap();
+        profile.compile_flags = vec!["-pipe".to_string()];
+        let gcc = GccCompiler { profile, ..compiler("gcc", 1) };
+
+        let mut app = project("app", ProjectType::Executable, &[]);
+        app.defines = vec!["FOO".to_string(), "BAR=2".to_string()];
+        app.c_standard = Some(CStandard::C23);
+        app.cxx_standard = Some(CxxStandard::Cxx20);
+        app.warning_level = WarningLevel::Extra;
+        app.warnings_as_errors = true;
+        app.optimization = Some(Optimization::Size);
+        app.compile_flags = vec!["-fno-common".to_string()];
+
+        let command = gcc.compile_command(&app, Path::new("/app/main.c"), Path::new("/out/main.c.o"), &[PathBuf::from("/inc")]);
+        assert_eq!(
+            command_line(&command),
+            [
+                "gcc", "-c", "/app/main.c", "-o", "/out/main.c.o", "-MMD", "-MF", "/out/main.c.d", "-Os", "-std=c2x", "-Wall", "-Wextra",
+                "-Werror", "-DNDEBUG", "-DFOO", "-DBAR=2", "-I", "/inc", "-pipe", "-fno-common",
+            ]
+        );
+
+        app.warning_level = WarningLevel::None;
+        let command = command_line(&gcc.compile_command(&app, Path::new("/app/main.cpp"), Path::new("/out/main.cpp.o"), &[]));
+        assert!(command.contains(&"-std=c++20".to_string()) && command.contains(&"-w".to_string()));
+        assert!(!command.contains(&"-Wall".to_string()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shared_libraries_are_versioned_and_found_relative_to_their_users() {
+        use std::fs::{create_dir_all, read_link, read_to_string, remove_dir_all, write};
+        use std::path::Path;
+        use std::time::Duration;
+        use crate::test_fixtures::stand_in_driver;
+
+        let names = |version: &str| {
+            let mut foo = project("foo", ProjectType::DynamicLib, &[]);
+            foo.version = version.to_string();
+            GccCompiler::shared_library_names(&foo)
+        };
+        assert_eq!(names("1.2.3"), ("libfoo.so.1.2.3".to_string(), "libfoo.so.1".to_string(), "libfoo.so".to_string()));
+        assert_eq!(names(""), ("libfoo.so".to_string(), "libfoo.so".to_string(), "libfoo.so".to_string()));
+        assert_eq!(names("2.0.0-rc.1"), ("libfoo.so.2.0.0-rc.1".to_string(), "libfoo.so.2".to_string(), "libfoo.so".to_string()));
+
+        // app -> foo (shared) -> st (static)
+        let root = temp_dir("gcc_shared");
+        let gcc = stand_in_driver(&root, "gcc", Duration::ZERO);
+        create_dir_all(root.join("foo")).unwrap();
+        create_dir_all(root.join("app")).unwrap();
+        write(root.join("foo/foo.c"), "int foo(void) { return 1; }\n").unwrap();
+        write(root.join("app/main.c"), "int main(void) { return 0; }\n").unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let st = project("st", ProjectType::StaticLib, &[]);
+        let mut foo = project("foo", ProjectType::DynamicLib, &["st"]);
+        foo.version = "1.2.3".to_string();
+        let app = project("app", ProjectType::Executable, &["foo"]);
+        let dir = |p| project_output_dir(&root, p, "debug", TargetPlatform::Linux, TargetArch::X64);
+        create_dir_all(dir(&st)).unwrap();
+        write(dir(&st).join("libst.a"), "").unwrap();
+
+        let compiler = compiler(gcc.to_str().unwrap(), 1);
+        let sol = solution(Vec::new());
+        compiler.compile_project(&foo, &sol, &root, Vec::new(), false).unwrap();
+        compiler.link_project(&foo, &sol, &root, std::slice::from_ref(&st), false).unwrap();
+        compiler.compile_project(&app, &sol, &root, Vec::new(), false).unwrap();
+        compiler.link_project(&app, &sol, &root, &[st.clone(), foo.clone()], false).unwrap();
+
+        let object = read_to_string(dir(&foo).join("foo.c.o")).unwrap();
+        let foo_link = read_to_string(dir(&foo).join("libfoo.so.1.2.3")).unwrap();
+        let soname_link = read_link(dir(&foo).join("libfoo.so.1")).unwrap();
+        let linker_name_link = read_link(dir(&foo).join("libfoo.so")).unwrap();
+        let app_link = read_to_string(dir(&app).join("app")).unwrap();
+        remove_dir_all(&root).unwrap();
+
+        assert!(object.contains(" -fPIC"));
+        assert!(foo_link.contains(" -shared -Wl,-soname,libfoo.so.1 "));
+        assert!(foo_link.contains(&dir(&st).join("libst.a").display().to_string()));
+        assert_eq!(soname_link, Path::new("libfoo.so.1.2.3"));
+        assert_eq!(linker_name_link, Path::new("libfoo.so.1"));
+        assert!(app_link.contains(&format!(" -L {} -lfoo -Wl,-rpath,$ORIGIN/../foo ", dir(&foo).display())));
+        // st is inside libfoo already
+        assert!(!app_link.contains("libst.a"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn static_libraries_are_archived_afresh_and_linked_dependents_first() {
+        use std::fs::{create_dir_all, hard_link, read_to_string, remove_dir_all, write};
+        use std::time::Duration;
+        use crate::test_fixtures::stand_in_driver;
+
+        // app -> net (static) -> core (static)
+        let root = temp_dir("gcc_static");
+        let gcc = stand_in_driver(&root, "gcc", Duration::ZERO);
+        let ar = stand_in_driver(&root, "ar", Duration::ZERO);
+        for (dir, source) in [("core", "core.c"), ("net", "net.c"), ("app", "main.c")] {
+            create_dir_all(root.join(dir)).unwrap();
+            write(root.join(dir).join(source), "int f(void) { return 0; }\n").unwrap();
+        }
+        let root = root.canonicalize().unwrap();
+
+        let core = project("core", ProjectType::StaticLib, &[]);
+        let net = project("net", ProjectType::StaticLib, &["core"]);
+        let app = project("app", ProjectType::Executable, &["net"]);
+        let dir = |p| project_output_dir(&root, p, "debug", TargetPlatform::Linux, TargetArch::X64);
+
+        // An archive left by an earlier build, shared with a witness to tell whether it was rewritten in place.
+        create_dir_all(dir(&net)).unwrap();
+        write(dir(&net).join("libnet.a"), "stale").unwrap();
+        hard_link(dir(&net).join("libnet.a"), root.join("witness")).unwrap();
+
+        let compiler = GccCompiler { ar_path: ar.to_str().unwrap().to_string(), ..compiler(gcc.to_str().unwrap(), 1) };
+        let sol = solution(Vec::new());
+        let dependencies = [core.clone(), net.clone()];
+        for (project, dependencies) in [(&core, &dependencies[..0]), (&net, &dependencies[..1]), (&app, &dependencies[..])] {
+            compiler.compile_project(project, &sol, &root, Vec::new(), false).unwrap();
+            compiler.link_project(project, &sol, &root, dependencies, false).unwrap();
         }
+
+        let net_archive = read_to_string(dir(&net).join("libnet.a")).unwrap();
+        let witness = read_to_string(root.join("witness")).unwrap();
+        let core_archived = dir(&core).join("libcore.a").exists();
+        let app_link = read_to_string(dir(&app).join("app")).unwrap();
+        remove_dir_all(&root).unwrap();
+
+        assert_eq!(net_archive, format!("rcsD {} {}\n", dir(&net).join("libnet.a").display(), dir(&net).join("net.c.o").display()));
+        assert_eq!(witness, "stale");
+        assert!(core_archived);
+        assert!(app_link.ends_with(&format!(
+            "{} {} {} -o {}\n",
+            dir(&app).join("main.c.o").display(),
+            dir(&net).join("libnet.a").display(),
+            dir(&core).join("libcore.a").display(),
+            dir(&app).join("app").display()
+        )));
     }
-}
\ No newline at end of file
+
+    #[cfg(unix)]
+    #[test]
+    fn same_named_sources_compile_concurrently_to_distinct_objects() {
+        use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+        use std::time::Duration;
+        use crate::test_fixtures::stand_in_driver;
+
+        // Slow enough for the two compilations to overlap.
+        let root = temp_dir("gcc_same_name");
+        let gcc = stand_in_driver(&root, "gcc", Duration::from_millis(200));
+        create_dir_all(root.join("app/core/sub")).unwrap();
+        write(root.join("app/core/core.c"), "int a(void) { return 1; }\n").unwrap();
+        write(root.join("app/core/sub/core.c"), "int b(void) { return 2; }\n").unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let app = project("app", ProjectType::Executable, &[]);
+        let compiler = compiler(gcc.to_str().unwrap(), 2);
+        let result = compiler.compile_project(&app, &solution(Vec::new()), &root, Vec::new(), false);
+
+        let output_dir = project_output_dir(&root, &app, "debug", TargetPlatform::Linux, TargetArch::X64);
+        let objects: Vec<PathBuf> = BuildState::load(&output_dir).compiled.values().map(|r| r.object.clone()).collect();
+        let contents: Vec<String> = objects.iter().map(|o| read_to_string(o).unwrap_or_default()).collect();
+        remove_dir_all(&root).unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!(objects, [output_dir.join("core/core.c.o"), output_dir.join("core/sub/core.c.o")]);
+        assert!(contents[0].starts_with(&format!("-c {} -o {} ", root.join("app/core/core.c").display(), objects[0].display())));
+        assert!(contents[1].starts_with(&format!("-c {} -o {} ", root.join("app/core/sub/core.c").display(), objects[1].display())));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn objects_of_deleted_sources_are_removed() {
+        use std::fs::{create_dir_all, remove_dir_all, remove_file, write};
+        use std::time::Duration;
+        use crate::test_fixtures::stand_in_driver;
+
+        let root = temp_dir("gcc_deleted_source");
+        let gcc = stand_in_driver(&root, "gcc", Duration::ZERO);
+        create_dir_all(root.join("app")).unwrap();
+        write(root.join("app/main.c"), "int main(void) { return 0; }\n").unwrap();
+        write(root.join("app/old.c"), "int old(void) { return 0; }\n").unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let app = project("app", ProjectType::Executable, &[]);
+        let compiler = compiler(gcc.to_str().unwrap(), 1);
+        let output_dir = project_output_dir(&root, &app, "debug", TargetPlatform::Linux, TargetArch::X64);
+
+        let first = compiler.compile_project(&app, &solution(Vec::new()), &root, Vec::new(), false);
+        let old_object_built = output_dir.join("old.c.o").exists();
+        remove_file(root.join("app/old.c")).unwrap();
+        let second = compiler.compile_project(&app, &solution(Vec::new()), &root, Vec::new(), false);
+        let old_object_kept = output_dir.join("old.c.o").exists();
+        let recorded = BuildState::load(&output_dir).compiled.len();
+        remove_dir_all(&root).unwrap();
+
+        assert!(first.is_ok() && second.is_ok());
+        assert!(old_object_built);
+        assert!(!old_object_kept);
+        assert_eq!(recorded, 1);
+    }
+}
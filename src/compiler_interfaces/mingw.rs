@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::compiler_interfaces::common::{CompileCommand, Compiler};
+use crate::compiler_interfaces::gcc::GccCompiler;
+use crate::compiler_interfaces::toolchain::{find_in_path, mingw_triple, Toolchain};
+use crate::helpers::job_pool::JobPool;
+use crate::solution::{BuildProfile, Project, Solution, TargetArch, TargetPlatform};
+
+///
+/// Backend cross compiling for Windows with MinGW-w64 (`x86_64-w64-mingw32-gcc`, `-g++` and `-ar`).
+/// MinGW-w64 is a GCC, so the work is delegated to a `GccCompiler` building for Windows:
+/// executables are `<name>.exe`, shared libraries `<name>.dll` along with their `lib<name>.dll.a`
+/// import library, and static libraries `lib<name>.a`.
+///
+pub struct MingwCompiler {
+    driver: GccCompiler,
+}
+
+impl MingwCompiler {
+    ///
+    /// Creates a MinGW-w64 backend.
+    /// # Arguments
+    /// * `toolchain` - The MinGW-w64 gcc, g++ and archiver to use.
+    /// * `arch` - The architecture to build for.
+    /// * `profile` - The build profile.
+    /// * `job_pool` - The job pool shared by the build.
+    ///
+    pub fn new(toolchain: Toolchain, arch: TargetArch, profile: BuildProfile, job_pool: Arc<JobPool>) -> Self {
+        MingwCompiler {
+            driver: GccCompiler {
+                gcc_path: toolchain.cc,
+                gcc_args: toolchain.cc_args,
+                gpp_path: toolchain.cxx,
+                gpp_args: toolchain.cxx_args,
+                ar_path: toolchain.ar,
+                linker: None,
+                platform: TargetPlatform::Windows,
+                arch,
+                target_flags: toolchain.target_flags,
+                profile,
+                job_pool,
+            },
+        }
+    }
+}
+
+impl Compiler for MingwCompiler {
+    fn compile_file(&self, project: &Project, abs_infile_path: &Path, abs_output_path: &Path, additional_includes: &[PathBuf], verbose: bool) -> Result<(), &'static str> {
+        self.driver.compile_file(project, abs_infile_path, abs_output_path, additional_includes, verbose)
+    }
+
+    fn compile_project(&self, project: &Project, solution: &Solution, solution_root: &Path, additional_include_directories: Vec<PathBuf>, verbose: bool) -> Result<(), &'static str> {
+        self.driver.compile_project(project, solution, solution_root, additional_include_directories, verbose)
+    }
+
+    fn compile_commands(&self, project: &Project, solution_root: &Path, additional_include_directories: &[PathBuf]) -> Result<Vec<CompileCommand>, &'static str> {
+        self.driver.compile_commands(project, solution_root, additional_include_directories)
+    }
+
+    fn link_project(&self, project: &Project, solution: &Solution, solution_root: &Path, dependencies: &[Project], verbose: bool) -> Result<(), &'static str> {
+        self.driver.link_project(project, solution, solution_root, dependencies, verbose)
+    }
+
+    fn detect_compiler_path() -> Option<String> {
+        find_in_path(&format!("{}-gcc", mingw_triple(TargetArch::X64))).map(|p| p.to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    #[test]
+    fn executables_link_against_dll_import_libraries_and_get_a_copy_of_the_dlls() {
+        use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+        use std::sync::Arc;
+        use std::time::Duration;
+        use super::MingwCompiler;
+        use crate::compiler_interfaces::common::{project_output_dir, Compiler};
+        use crate::compiler_interfaces::toolchain::Toolchain;
+        use crate::helpers::job_pool::JobPool;
+        use crate::solution::{BuildProfile, ProjectType, TargetArch, TargetPlatform};
+        use crate::test_fixtures::{project, solution, stand_in_driver, temp_dir};
+
+        let root = temp_dir("mingw_dll");
+        let gcc = stand_in_driver(&root, "x86_64-w64-mingw32-gcc", Duration::ZERO);
+        let gxx = stand_in_driver(&root, "x86_64-w64-mingw32-g++", Duration::ZERO);
+        create_dir_all(root.join("app")).unwrap();
+        create_dir_all(root.join("lib")).unwrap();
+        write(root.join("app/main.c"), "int main(void) { return 0; }\n").unwrap();
+        write(root.join("lib/lib.c"), "int lib(void) { return 0; }\n").unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let toolchain = Toolchain {
+            cc: gcc.to_string_lossy().into_owned(),
+            cc_args: Vec::new(),
+            cxx: gxx.to_string_lossy().into_owned(),
+            cxx_args: Vec::new(),
+            ar: "x86_64-w64-mingw32-ar".to_string(),
+            target_flags: Vec::new(),
+        };
+        let compiler = MingwCompiler::new(toolchain, TargetArch::X64, BuildProfile::builtin("debug").unwrap(), Arc::new(JobPool::new(1, false)));
+        let lib = project("lib", ProjectType::DynamicLib, &[]);
+        let app = project("app", ProjectType::Executable, &["lib"]);
+        let sol = solution(vec![app.clone(), lib.clone()]);
+
+        compiler.compile_project(&lib, &sol, &root, Vec::new(), false).unwrap();
+        compiler.link_project(&lib, &sol, &root, &[], false).unwrap();
+        compiler.compile_project(&app, &sol, &root, Vec::new(), false).unwrap();
+        compiler.link_project(&app, &sol, &root, std::slice::from_ref(&lib), false).unwrap();
+
+        let lib_dir = project_output_dir(&root, &lib, "debug", TargetPlatform::Windows, TargetArch::X64);
+        let app_dir = project_output_dir(&root, &app, "debug", TargetPlatform::Windows, TargetArch::X64);
+        let lib_link = read_to_string(lib_dir.join("lib.dll")).unwrap();
+        let app_link = read_to_string(app_dir.join("app.exe")).unwrap();
+        let lib_compile = read_to_string(lib_dir.join("lib.c.o")).unwrap();
+        let import_library_written = lib_dir.join("liblib.dll.a").is_file();
+        let dll_copied = app_dir.join("lib.dll").is_file();
+        remove_dir_all(&root).unwrap();
+
+        assert!(lib_link.contains("-shared") && lib_link.contains(&format!("-Wl,--out-implib,{}", lib_dir.join("liblib.dll.a").display())));
+        assert!(!lib_link.contains("-soname") && !lib_compile.contains("-fPIC"));
+        assert!(import_library_written);
+        assert!(app_link.contains(&lib_dir.join("liblib.dll.a").display().to_string()) && !app_link.contains("rpath"));
+        assert!(dll_copied);
+    }
+}
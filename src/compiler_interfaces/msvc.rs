@@ -1,17 +1,466 @@
-use std::fs::exists;
-use std::path::{Path, PathBuf};
-use std::process::Command;
 use std::env;
+use std::ffi::OsString;
+use std::fs::{exists, read_dir};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::Arc;
+
+use crate::build_state::{command_line, hash_files, BuildState, LinkRecord};
+use crate::compiler_interfaces::common::{compile_source, compile_sources, copy_runtime_dlls, project_output_dir, source_compile_commands, CompileCommand, Compiler, SourceCompiler};
+use crate::compiler_interfaces::toolchain::{find_program, ToolchainConfig};
+use crate::dependency_manager::local_resolve::{link_dependencies, resolve_link_libraries, LinkLibraries};
+use crate::helpers::console::Console;
+use crate::helpers::depfile_tools::write_depfile;
+use crate::helpers::job_pool::JobPool;
+use crate::solution::{BuildProfile, CStandard, CxxStandard, Optimization, WarningLevel, Project, ProjectType, Solution, TargetArch, TargetPlatform};
+
+// Prefix of the lines `cl /showIncludes` prints for every included file.
+const SHOW_INCLUDES_PREFIX: &str = "Note: including file:";
+
+///
+/// Backend for Microsoft's toolchain: `cl.exe` compiles, `link.exe` links executables and DLLs
+/// and `lib.exe` archives static libraries.
+/// Executables are `<name>.exe`, shared libraries `<name>.dll` along with their `<name>.lib` import library,
+/// and static libraries `<name>.lib`.
+///
+pub struct MsvcCompiler {
+    /// Compiler driver (`cl.exe`).
+    pub cl_path: String,
+    /// Linker (`link.exe`), for executables and DLLs.
+    pub link_path: String,
+    /// Librarian (`lib.exe`), for static libraries.
+    pub lib_path: String,
+    /// Variables set up the way `vcvarsall.bat` does (`INCLUDE`, `LIB`, `PATH`), applied to every command.
+    pub environment: Vec<(String, OsString)>,
+    /// Architecture this compiler builds for. Selects the output directory and `/MACHINE`.
+    pub arch: TargetArch,
+    /// Build profile: optimisation, debug info, defines and extra flags. Selects the output directory.
+    pub profile: BuildProfile,
+    pub job_pool: Arc<JobPool>,
+}
+
+/// A Windows 10+ SDK installation, providing the system headers and import libraries.
+pub struct WindowsSdk {
+    /// `Windows Kits\10`.
+    pub root: PathBuf,
+    /// Version directory used in `Include`, `Lib` and `bin` (e.g. `10.0.22621.0`).
+    pub version: String,
+}
+
+impl MsvcCompiler {
+    ///
+    /// Finds `cl.exe`, `link.exe` and `lib.exe` for `arch`.
+    /// Programs set in the solution's `toolchain` block (`cc`, `link` and `ar`) are used as-is,
+    /// the others are taken from the latest Visual Studio installation found by `detect_msvc_path`.
+    /// # Arguments
+    /// * `config` - The solution's `toolchain` block.
+    /// * `arch` - The architecture to build for.
+    /// * `profile` - The build profile.
+    /// * `job_pool` - The job pool shared by the build.
+    /// * `verbose` - Whether to enable verbose output.
+    /// # Returns
+    /// * The backend, or an error describing which program could not be found.
+    ///
+    pub fn discover(config: &ToolchainConfig, arch: TargetArch, profile: BuildProfile, job_pool: Arc<JobPool>, verbose: bool) -> Result<Self, String> {
+        let installation = detect_msvc_path().map(PathBuf::from);
+        if let Err(e) = &installation {
+            Console::log_verbose(format!("No MSVC installation: {}", e).as_str(), verbose);
+        }
+
+        let tool = |configured: &Option<String>, config_key: &str, exe: &str| -> Result<String, String> {
+            if let Some(configured) = configured {
+                return find_program(configured)
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .ok_or(format!("`{}` set in the solution's toolchain.{} was not found", configured, config_key));
+            }
+
+            match &installation {
+                Ok(tools_dir) => {
+                    let path = msvc_bin_dir(tools_dir, arch).join(exe);
+                    if path.is_file() {
+                        Ok(path.to_string_lossy().into_owned())
+                    } else {
+                        Err(format!("{} not found: {} does not exist", exe, path.display()))
+                    }
+                }
+                Err(e) => Err(format!("{} not found: {} Set toolchain.{} in the solution to use another one", exe, e, config_key)),
+            }
+        };
+
+        let cl_path = tool(&config.cc, "cc", "cl.exe")?;
+        let link_path = tool(&config.link, "link", "link.exe")?;
+        let lib_path = tool(&config.ar, "ar", "lib.exe")?;
+
+        let environment = match &installation {
+            Ok(tools_dir) => {
+                let sdk = detect_windows_sdk();
+                if sdk.is_none() {
+                    Console::log_warning("No Windows SDK found; only the MSVC headers and libraries will be available");
+                }
+                msvc_environment(tools_dir, sdk.as_ref(), arch)
+            }
+            Err(_) => Vec::new(),
+        };
+
+        Console::log_verbose(format!("Using cl {}, link {} and lib {}", cl_path, link_path, lib_path).as_str(), verbose);
+
+        Ok(MsvcCompiler { cl_path, link_path, lib_path, environment, arch, profile, job_pool })
+    }
+
+    /// Returns the final artifact of `project` in `abs_output_dir`: `<name>.lib`, `<name>.dll` or `<name>.exe`.
+    pub fn artifact_path(project: &Project, abs_output_dir: &Path) -> PathBuf {
+        match project.project_type {
+            ProjectType::StaticLib => abs_output_dir.join(format!("{}.lib", project.name)),
+            ProjectType::DynamicLib => abs_output_dir.join(format!("{}.dll", project.name)),
+            ProjectType::Executable => abs_output_dir.join(format!("{}.exe", project.name)),
+        }
+    }
+
+    /// Returns the import library written next to a DLL (`foo.dll` -> `foo.lib`). Dependents link against it.
+    pub fn import_library_path(project: &Project, abs_output_dir: &Path) -> PathBuf {
+        abs_output_dir.join(format!("{}.lib", project.name))
+    }
+
+    /// Value of `/MACHINE` for `arch`.
+    pub fn machine(arch: TargetArch) -> &'static str {
+        match arch {
+            TargetArch::X86 => "X86",
+            TargetArch::X64 => "X64",
+            TargetArch::ARM => "ARM",
+            TargetArch::ARM64 => "ARM64",
+        }
+    }
+
+
+    ///
+    /// Crafts the command producing the artifact of `project` from `objects`:
+    /// `lib.exe` for static libraries, `link.exe` (with `/DLL` and `/IMPLIB` for shared libraries) otherwise.
+    /// # Arguments
+    /// * `project` - The project to link.
+    /// * `abs_output_dir` - The project's output directory.
+    /// * `objects` - The project's object files.
+    /// * `libraries` - Static and import libraries of the dependencies, dependents first. Ignored for static libraries.
+    /// * `link_libraries` - System and prebuilt libraries. Ignored for static libraries.
+    ///
+    pub fn link_command(&self, project: &Project, abs_output_dir: &Path, objects: &[PathBuf], libraries: &[PathBuf], link_libraries: &LinkLibraries) -> Command {
+        let output_artifact = MsvcCompiler::artifact_path(project, abs_output_dir);
+
+        let mut command = if project.project_type == ProjectType::StaticLib {
+            Command::new(&self.lib_path)
+        } else {
+            Command::new(&self.link_path)
+        };
+        command.envs(self.environment.iter().map(|(k, v)| (k, v)));
+        command.current_dir(abs_output_dir);
+        command.arg("/nologo").arg(format!("/MACHINE:{}", MsvcCompiler::machine(self.arch)));
+
+        if project.project_type != ProjectType::StaticLib {
+            if self.profile.debug_info {
+                command.arg("/DEBUG");
+            }
+            command.args(&self.profile.link_flags);
+            command.args(&project.link_flags);
+        }
+
+        if project.project_type == ProjectType::DynamicLib {
+            let import_library = MsvcCompiler::import_library_path(project, abs_output_dir);
+            command.arg("/DLL").arg(format!("/IMPLIB:{}", import_library.display()));
+        }
+
+        command.arg(format!("/OUT:{}", output_artifact.display()));
+        command.args(objects);
+
+        if project.project_type != ProjectType::StaticLib {
+            command.args(libraries);
+            for dir in &link_libraries.library_dirs {
+                command.arg(format!("/LIBPATH:{}", dir.display()));
+            }
+            command.args(&link_libraries.prebuilt);
+            for name in &link_libraries.system {
+                if name.to_lowercase().ends_with(".lib") {
+                    command.arg(name);
+                } else {
+                    command.arg(format!("{}.lib", name));
+                }
+            }
+        }
+
+        command
+    }
 
-use crate::solution::Project;
+    // Headers under the `INCLUDE` directories are system headers, which are not tracked (like `gcc -MMD`).
+    fn system_include_dirs(&self) -> Vec<String> {
+        let include = self
+            .environment
+            .iter()
+            .find(|(k, _)| k == "INCLUDE")
+            .map(|(_, v)| v.clone())
+            .or_else(|| env::var_os("INCLUDE"))
+            .unwrap_or_default();
 
-// ======== IMPORTANT INFO ABOUT THIS FILE =======
-// This file is only a placeholder for real implementation of the MSVC compiler..
-// All the `noinspection RsLiveness` and `noinspection RsExternalLinter` clauses will be removed when working on MSVC compilation
-// ===============================================
+        env::split_paths(&include)
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(|p| p.to_string_lossy().to_lowercase())
+            .collect()
+    }
+}
+
+impl SourceCompiler for MsvcCompiler {
+    const OBJECT_EXTENSION: &'static str = "obj";
+
+    fn compiler_path(&self) -> &str {
+        &self.cl_path
+    }
+
+    fn arch(&self) -> TargetArch {
+        self.arch
+    }
+
+    fn job_pool(&self) -> &JobPool {
+        &self.job_pool
+    }
+
+    fn output_dir(&self, abs_solution_root: &Path, project: &Project) -> PathBuf {
+        project_output_dir(abs_solution_root, project, &self.profile.name, TargetPlatform::Windows, self.arch)
+    }
+
+    fn compile_command(&self, project: &Project, abs_infile_path: &Path, abs_output_path: &Path, additional_includes: &[PathBuf]) -> Command {
+        let cpp = abs_infile_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("cpp") || e.eq_ignore_ascii_case("cc") || e.eq_ignore_ascii_case("cxx"));
+
+        let mut command = Command::new(&self.cl_path);
+        command.envs(self.environment.iter().map(|(k, v)| (k, v)));
+        if let Some(parent) = abs_output_path.parent() {
+            command.current_dir(parent);
+        }
+
+        command.arg("/nologo").arg("/c");
+        // cl only guesses the language from `.c`, `.cpp` and `.cxx`: say it explicitly.
+        if cpp {
+            command.arg("/TP").arg("/EHsc");
+        } else {
+            command.arg("/TC");
+        }
+        command
+            .arg(abs_infile_path)
+            .arg(format!("/Fo{}", abs_output_path.display()))
+            // Lists the included headers so editing one of them triggers a rebuild.
+            .arg("/showIncludes");
+
+        command.arg(match project.optimization(&self.profile) {
+            Optimization::None => "/Od",
+            Optimization::Speed => "/O2",
+            Optimization::Size => "/O1",
+        });
+        // /Z7 keeps the debug info in each object, so parallel compilations don't fight over a .pdb.
+        if self.profile.debug_info {
+            command.arg("/Z7");
+        }
+
+        // cl has no switch for C89/C99 (its default) nor for C++ before C++14.
+        let standard = if cpp {
+            project.cxx_standard.map(|s| match s {
+                CxxStandard::Cxx11 | CxxStandard::Cxx14 => "c++14",
+                CxxStandard::Cxx17 => "c++17",
+                CxxStandard::Cxx20 => "c++20",
+                CxxStandard::Cxx23 => "c++latest",
+            })
+        } else {
+            project.c_standard.and_then(|s| match s {
+                CStandard::C89 | CStandard::C99 => None,
+                CStandard::C11 => Some("c11"),
+                CStandard::C17 => Some("c17"),
+                CStandard::C23 => Some("clatest"),
+            })
+        };
+        if let Some(standard) = standard {
+            command.arg(format!("/std:{}", standard));
+        }
 
+        match project.warning_level {
+            WarningLevel::None => { command.arg("/W0"); }
+            WarningLevel::Default => {}
+            WarningLevel::All => { command.arg("/W3"); }
+            WarningLevel::Extra => { command.arg("/W4"); }
+        }
+        if project.warnings_as_errors {
+            command.arg("/WX");
+        }
 
-//noinspection RsExternalLinter
+        for define in self.profile.defines.iter().chain(&project.defines) {
+            command.arg(format!("/D{}", define));
+        }
+
+        for include_path in additional_includes {
+            command.arg(format!("/I{}", include_path.display()));
+        }
+
+        command.args(&self.profile.compile_flags);
+        command.args(&project.compile_flags);
+
+        command
+    }
+
+    // /showIncludes notes are turned into a depfile instead of being printed.
+    fn filter_output(&self, abs_infile_path: &Path, abs_output_path: &Path, output: &Output) -> Result<Vec<u8>, &'static str> {
+        let system_include_dirs = self.system_include_dirs();
+        let mut headers: Vec<PathBuf> = vec![abs_infile_path.to_path_buf()];
+        let mut messages = String::new();
+
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            match line.strip_prefix(SHOW_INCLUDES_PREFIX) {
+                Some(header) => {
+                    let header = header.trim();
+                    let lowercase = header.to_lowercase();
+                    if !system_include_dirs.iter().any(|dir| lowercase.starts_with(dir.as_str())) {
+                        headers.push(PathBuf::from(header));
+                    }
+                }
+                None => {
+                    messages.push_str(line);
+                    messages.push('\n');
+                }
+            }
+        }
+
+        if output.status.success() {
+            write_depfile(&self.depfile_path(abs_output_path), abs_output_path, &headers)
+                .map_err(|_| "Failed to write dependency file")?;
+        }
+        Ok(messages.into_bytes())
+    }
+}
+
+impl Compiler for MsvcCompiler {
+    fn compile_file(&self, project: &Project, abs_infile_path: &Path, abs_output_path: &Path, additional_includes: &[PathBuf], verbose: bool) -> Result<(), &'static str> {
+        compile_source(self, project, abs_infile_path, abs_output_path, additional_includes, verbose)
+    }
+
+    fn compile_project(&self, project: &Project, _solution: &Solution, solution_root: &Path, include_directories: Vec<PathBuf>, verbose: bool) -> Result<(), &'static str> {
+        compile_sources(self, project, solution_root, &include_directories, verbose)
+    }
+
+    fn compile_commands(&self, project: &Project, solution_root: &Path, include_directories: &[PathBuf]) -> Result<Vec<CompileCommand>, &'static str> {
+        source_compile_commands(self, project, solution_root, include_directories)
+    }
+
+    fn link_project(
+        &self, project: &Project,
+        _solution: &Solution,
+        solution_root: &Path,
+        dependencies: &[Project],
+        verbose: bool) -> Result<(), &'static str> {
+
+        let abs_solution_root = solution_root.canonicalize().map_err(|_| "Failed to canonicalize solution root path")?;
+
+        let abs_project_output_path = &project_output_dir(&abs_solution_root, project, &self.profile.name, TargetPlatform::Windows, self.arch)
+            .canonicalize()
+            .map_err(|_| "Project Output Path not found")?;
+        let mut state = BuildState::load(abs_project_output_path);
+
+        let object_files: Vec<PathBuf> = state.compiled.values().map(|r| r.object.clone()).collect();
+
+        if object_files.is_empty() {
+            Console::log_warning(format!("No object files were found in directory: {}. This may be unintended behavior", abs_project_output_path.display()).as_str());
+            return Ok(());
+        }
+
+        let output_artifact = MsvcCompiler::artifact_path(project, abs_project_output_path);
+
+        // Dependents before dependencies, like the GCC backend (link.exe itself doesn't care about the order).
+        // Libraries behind a DLL were linked into it already.
+        let mut libraries: Vec<PathBuf> = Vec::new();
+        for dep in link_dependencies(project, dependencies) {
+            let dep_output_dir = project_output_dir(&abs_solution_root, dep, &self.profile.name, TargetPlatform::Windows, self.arch);
+
+            match dep.project_type {
+                ProjectType::StaticLib => libraries.push(MsvcCompiler::artifact_path(dep, &dep_output_dir)),
+                ProjectType::DynamicLib => libraries.push(MsvcCompiler::import_library_path(dep, &dep_output_dir)),
+                ProjectType::Executable => {}
+            }
+        }
+
+        // System and prebuilt libraries, ours and the ones our static libraries need.
+        let link_libraries = resolve_link_libraries(project, dependencies, &abs_solution_root);
+        let mut command = self.link_command(project, abs_project_output_path, &object_files, &libraries, &link_libraries);
+
+        let mut inputs = object_files.clone();
+        if project.project_type != ProjectType::StaticLib {
+            inputs.extend(libraries.iter().cloned());
+            inputs.extend(link_libraries.prebuilt.iter().cloned());
+        }
+
+        let link_record = LinkRecord {
+            command: command_line(&command),
+            inputs: hash_files(&inputs)?,
+            output: output_artifact.clone(),
+        };
+
+        if project.project_type == ProjectType::Executable {
+            // Every DLL is loaded at runtime, even those only other DLLs use.
+            let dlls: Vec<PathBuf> = dependencies
+                .iter()
+                .filter(|d| d.project_type == ProjectType::DynamicLib)
+                .map(|d| MsvcCompiler::artifact_path(d, &project_output_dir(&abs_solution_root, d, &self.profile.name, TargetPlatform::Windows, self.arch)))
+                .collect();
+            state.produced.extend(copy_runtime_dlls(&dlls, abs_project_output_path, verbose)?);
+        }
+
+        if state.is_link_up_to_date(&link_record) {
+            Console::log_info(&format!("Artifact is up to date: {}", output_artifact.display()));
+            return state.save(abs_project_output_path);
+        }
+
+        Console::log_info(&format!("Linking: {}", output_artifact.display()));
+        Console::log_verbose(&format!("Linking command: {:?}", command), verbose);
+
+        let output = command
+            .output()
+            .map_err(|_| "Failed to execute the linker")?;
+
+        Console::log_info(&format!("status: {}", output.status));
+
+        // link.exe and lib.exe report their errors on stdout.
+        io::stdout().write_all(&output.stdout).map_err(|_| "Failed to write to stdout")?;
+        io::stderr().write_all(&output.stderr).map_err(|_| "Failed to write to stderr")?;
+        if output.status.success() {
+            if project.project_type == ProjectType::DynamicLib {
+                state.produced.insert(MsvcCompiler::import_library_path(project, abs_project_output_path));
+            }
+            // Written by link.exe depending on the flags: DLL exports, debug information, incremental link data.
+            if project.project_type != ProjectType::StaticLib {
+                for extension in ["exp", "pdb", "ilk"] {
+                    let side_file = output_artifact.with_extension(extension);
+                    if side_file.exists() {
+                        state.produced.insert(side_file);
+                    }
+                }
+            }
+
+            state.link = Some(link_record);
+            state.save(abs_project_output_path)?;
+            Console::log_success("Linked successfully.");
+            Ok(())
+        } else {
+            Err("Linking failed.")
+        }
+    }
+
+    fn detect_compiler_path() -> Option<String> {
+        let tools_dir = detect_msvc_path().ok()?;
+        let cl = msvc_bin_dir(Path::new(&tools_dir), TargetArch::X64).join("cl.exe");
+        cl.is_file().then(|| cl.to_string_lossy().into_owned())
+    }
+}
+
+///
+/// Locates the latest MSVC toolset of the latest Visual Studio installation.
+/// # Returns
+/// * The toolset directory (`<Visual Studio>\VC\Tools\MSVC\<version>`), or an error message.
+///
 pub fn detect_msvc_path() -> Result<String, &'static str> {
 
     // Using vswhere to locate latest Visual Studio installation
@@ -30,7 +479,7 @@ pub fn detect_msvc_path() -> Result<String, &'static str> {
 
     // Real stuff: getting the installation path
     let output = Command::new(vswhere)
-        .args(&["-latest", "-products", "*", "-requires", "Microsoft.VisualStudio.Component.VC.Tools.x86.x64", "-property", "installationPath"])
+        .args(["-latest", "-products", "*", "-requires", "Microsoft.VisualStudio.Component.VC.Tools.x86.x64", "-property", "installationPath"])
         .output()
         .map_err(|_| "Failed to execute vswhere")?;
 
@@ -49,41 +498,229 @@ pub fn detect_msvc_path() -> Result<String, &'static str> {
         .join("Tools")
         .join("MSVC");
 
-    // You'll need to find the latest MSVC version directory here
-    // For now, returning the base path as a placeholder
-    if exists(&msvc_base).unwrap_or(false) {
-        msvc_base.to_str()
-            .ok_or("Failed to convert path to string")
-            .map(|s| s.to_string())
-    } else {
-        Err("MSVC path does not exist")
+    if !exists(&msvc_base).unwrap_or(false) {
+        return Err("MSVC path does not exist");
     }
+
+    latest_version_dir(&msvc_base)
+        .ok_or("No MSVC toolset found in the Visual Studio installation")?
+        .to_str()
+        .ok_or("Failed to convert path to string")
+        .map(|s| s.to_string())
 }
 
-//noinspection RsExternalLinter
-//noinspection RsLiveness
-fn compile(path: &Path) -> Result<(), &'static str> {
+/// Locates the latest Windows 10+ SDK, in `%ProgramFiles(x86)%\Windows Kits\10`.
+pub fn detect_windows_sdk() -> Option<WindowsSdk> {
+    let program_files_x86 = env::var("ProgramFiles(x86)").unwrap_or("C:\\Program Files (x86)".to_string());
+    let root = PathBuf::from(program_files_x86).join("Windows Kits").join("10");
+
+    let include = latest_version_dir(&root.join("Include"))?;
+    let version = include.file_name()?.to_string_lossy().into_owned();
+    Some(WindowsSdk { root, version })
+}
 
-    Ok(())
+///
+/// Returns the directory holding the MSVC programs building for `arch` on this host
+/// (e.g. `<tools_dir>\bin\Hostx64\arm64`).
+///
+pub fn msvc_bin_dir(tools_dir: &Path, arch: TargetArch) -> PathBuf {
+    tools_dir.join("bin").join(format!("Host{}", host_arch_dir())).join(arch.name())
 }
 
-//noinspection RsExternalLinter
-//noinspection RsLiveness
-fn link(files: &Vec<&Path>) -> Result<(), &'static str> {
+///
+/// Builds the environment `vcvarsall.bat` would set up to build for `arch`.
+/// # Arguments
+/// * `tools_dir` - The MSVC toolset directory returned by `detect_msvc_path`.
+/// * `sdk` - The Windows SDK, if one was found.
+/// * `arch` - The architecture to build for.
+/// # Returns
+/// * `INCLUDE`, `LIB` and `PATH`, the latter extended with the current `PATH`.
+///
+pub fn msvc_environment(tools_dir: &Path, sdk: Option<&WindowsSdk>, arch: TargetArch) -> Vec<(String, OsString)> {
+    let mut include = vec![tools_dir.join("include")];
+    let mut lib = vec![tools_dir.join("lib").join(arch.name())];
+    let mut path = vec![msvc_bin_dir(tools_dir, arch)];
 
-    Ok(())
+    if let Some(sdk) = sdk {
+        let sdk_include = sdk.root.join("Include").join(&sdk.version);
+        let sdk_lib = sdk.root.join("Lib").join(&sdk.version);
+        include.extend(["ucrt", "shared", "um", "winrt"].iter().map(|d| sdk_include.join(d)));
+        lib.extend(["ucrt", "um"].iter().map(|d| sdk_lib.join(d).join(arch.name())));
+        path.push(sdk.root.join("bin").join(&sdk.version).join(host_arch_dir()));
+    }
+
+    path.extend(env::var_os("PATH").map(|p| env::split_paths(&p).collect::<Vec<_>>()).unwrap_or_default());
+
+    // Paths coming from the installation never contain the separator, so joining cannot fail.
+    let join = |paths: Vec<PathBuf>| env::join_paths(paths).unwrap_or_default();
+    vec![
+        ("INCLUDE".to_string(), join(include)),
+        ("LIB".to_string(), join(lib)),
+        ("PATH".to_string(), join(path)),
+    ]
 }
 
-//noinspection RsExternalLinter
-//noinspection RsLiveness
-pub fn build_project(project: Project) -> Result<(), &'static str> {
+// Name MSVC gives the host architecture in `bin\Host<arch>`.
+fn host_arch_dir() -> &'static str {
+    match env::consts::ARCH {
+        "x86" => "x86",
+        "aarch64" => "arm64",
+        _ => "x64",
+    }
+}
+
+// Returns the subdirectory of `dir` named after the highest version (`14.38.33130`, `10.0.22621.0`).
+fn latest_version_dir(dir: &Path) -> Option<PathBuf> {
+    read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let version: Option<Vec<u32>> = entry.file_name().to_string_lossy().split('.').map(|p| p.parse().ok()).collect();
+            version.map(|v| (v, entry.path()))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, path)| path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    use super::MsvcCompiler;
+    use crate::build_state::command_line;
+    use crate::compiler_interfaces::common::SourceCompiler;
+    use crate::dependency_manager::local_resolve::LinkLibraries;
+    use crate::helpers::job_pool::JobPool;
+    use crate::solution::{BuildProfile, Project, ProjectType, TargetArch};
+    use crate::test_fixtures;
+
+    fn compiler(cl_path: &str) -> MsvcCompiler {
+        MsvcCompiler {
+            cl_path: cl_path.to_string(),
+            link_path: "link.exe".to_string(),
+            lib_path: "lib.exe".to_string(),
+            environment: Vec::new(),
+            arch: TargetArch::X64,
+            profile: BuildProfile::builtin("release").unwrap(),
+            job_pool: Arc::new(JobPool::new(1, false)),
+        }
+    }
+
+    fn project(project_type: ProjectType) -> Project {
+        test_fixtures::project("foo", project_type, &[])
+    }
+
+    #[test]
+    fn compile_command_line() {
+        let command = compiler("cl.exe").compile_command(&project(ProjectType::Executable), Path::new("/src/main.cpp"), Path::new("/out/main.cpp.obj"), &[PathBuf::from("/inc")]);
+        assert_eq!(
+            command_line(&command),
+            ["cl.exe", "/nologo", "/c", "/TP", "/EHsc", "/src/main.cpp", "/Fo/out/main.cpp.obj", "/showIncludes", "/O2", "/DNDEBUG", "/I/inc"]
+        );
+    }
+
+    #[test]
+    fn project_settings_are_translated_to_cl_switches() {
+        use crate::solution::{CStandard, CxxStandard, Optimization, WarningLevel};
+
+        let mut foo = project(ProjectType::Executable);
+        foo.defines = vec!["FOO".to_string()];
+        foo.c_standard = Some(CStandard::C99);
+        foo.cxx_standard = Some(CxxStandard::Cxx23);
+        foo.warning_level = WarningLevel::Extra;
+        foo.warnings_as_errors = true;
+        foo.optimization = Some(Optimization::Size);
+        foo.compile_flags = vec!["/utf-8".to_string()];
+
+        let command = compiler("cl.exe").compile_command(&foo, Path::new("/src/main.cpp"), Path::new("/out/main.cpp.obj"), &[]);
+        assert_eq!(
+            command_line(&command),
+            ["cl.exe", "/nologo", "/c", "/TP", "/EHsc", "/src/main.cpp", "/Fo/out/main.cpp.obj", "/showIncludes", "/O1", "/std:c++latest", "/W4", "/WX", "/DNDEBUG", "/DFOO", "/utf-8"]
+        );
+
+        // cl has no switch for C99, its default
+        let command = command_line(&compiler("cl.exe").compile_command(&foo, Path::new("/src/main.c"), Path::new("/out/main.c.obj"), &[]));
+        assert!(command.contains(&"/TC".to_string()) && !command.iter().any(|a| a.starts_with("/std:")));
+    }
+
+    #[test]
+    fn link_command_lines() {
+        let objects = [PathBuf::from("/out/a.c.obj")];
+        let libraries = [PathBuf::from("/bar/bar.lib")];
+
+        let link_libraries = LinkLibraries {
+            library_dirs: vec![PathBuf::from("/vendor")],
+            prebuilt: vec![PathBuf::from("/vendor/zlib.lib")],
+            system: vec!["user32".to_string()],
+        };
+
+        let dll = compiler("cl.exe").link_command(&project(ProjectType::DynamicLib), Path::new("/out"), &objects, &libraries, &link_libraries);
+        assert_eq!(
+            command_line(&dll),
+            [
+                "link.exe", "/nologo", "/MACHINE:X64", "/DLL", "/IMPLIB:/out/foo.lib", "/OUT:/out/foo.dll", "/out/a.c.obj",
+                "/bar/bar.lib", "/LIBPATH:/vendor", "/vendor/zlib.lib", "user32.lib",
+            ]
+        );
+
+        let lib = compiler("cl.exe").link_command(&project(ProjectType::StaticLib), Path::new("/out"), &objects, &libraries, &link_libraries);
+        assert_eq!(command_line(&lib), ["lib.exe", "/nologo", "/MACHINE:X64", "/OUT:/out/foo.lib", "/out/a.c.obj"]);
+    }
+
+    #[test]
+    fn compile_commands_list_sources_without_creating_outputs() {
+        use std::fs::{create_dir_all, remove_dir_all, write};
+        use crate::compiler_interfaces::common::{project_output_dir, Compiler};
+        use crate::solution::TargetPlatform;
+
+        let root = test_fixtures::temp_dir("compile_commands");
+        create_dir_all(root.join("foo")).unwrap();
+        write(root.join("foo/main.cpp"), "int main() { return 0; }\n").unwrap();
+        write(root.join("foo/notes.txt"), "not a source\n").unwrap();
+        let root = root.canonicalize().unwrap();
 
-    let paths = vec![
-        Path::new("file1.o"),
-        Path::new("file2.o"),
-    ];
+        let commands = compiler("cl.exe").compile_commands(&project(ProjectType::Executable), &root, &[]).unwrap();
 
-    // link(&paths);
+        assert_eq!(commands.len(), 1);
+        let output_dir = project_output_dir(&root, &project(ProjectType::Executable), "release", TargetPlatform::Windows, TargetArch::X64);
+        assert_eq!(commands[0].file, root.join("foo/main.cpp"));
+        assert_eq!(commands[0].output, output_dir.join("main.cpp.obj"));
+        assert_eq!(commands[0].directory, output_dir);
+        assert_eq!(commands[0].arguments[0], "cl.exe");
+        assert!(!root.join("output").exists());
 
-    Ok(())
+        remove_dir_all(&root).unwrap();
+    }
+
+    // A stand-in cl records its arguments and reports one include, like the real one does with /showIncludes.
+    #[cfg(unix)]
+    #[test]
+    fn stand_in_compiler_records_arguments() {
+        use std::fs::{read_to_string, remove_dir_all, set_permissions, write, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+        use crate::compiler_interfaces::common::Compiler;
+        use crate::helpers::depfile_tools::read_depfile;
+
+        let dir = test_fixtures::temp_dir("msvc");
+        let cl = dir.join("cl");
+        write(&cl, "#!/bin/sh\nprintf '%s\\n' \"$@\" > \"$(dirname \"$0\")/args.txt\"\necho \"Note: including file: /inc/foo.h\"\n").unwrap();
+        set_permissions(&cl, Permissions::from_mode(0o755)).unwrap();
+        let source = dir.join("main.c");
+        write(&source, "int main(void) { return 0; }\n").unwrap();
+
+        compiler(cl.to_str().unwrap())
+            .compile_file(&project(ProjectType::Executable), &source, &dir.join("main.c.obj"), &[PathBuf::from("/inc")], false)
+            .unwrap();
+
+        let args = read_to_string(dir.join("args.txt")).unwrap();
+        let depfile = read_depfile(&dir.join("main.c.d"));
+        remove_dir_all(&dir).unwrap();
+
+        assert!(args.lines().any(|a| a == "/c"));
+        assert!(args.lines().any(|a| a == format!("/Fo{}", dir.join("main.c.obj").display())));
+        assert!(args.lines().any(|a| a == "/I/inc"));
+        assert_eq!(depfile, Some(vec![source, PathBuf::from("/inc/foo.h")]));
+    }
 }
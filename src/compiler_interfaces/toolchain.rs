@@ -0,0 +1,443 @@
+use std::env;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::compiler_interfaces::common::CompilerKind;
+use crate::dependency_manager::pkg_config::split_flags;
+use crate::helpers::console::Console;
+use crate::solution::{TargetArch, TargetPlatform};
+
+///
+/// Solution-level `toolchain` block. Each entry is either a path or a program name looked up in `PATH`.
+/// Entries that are not set are discovered automatically.
+///
+#[derive(Deserialize, Default, Clone)]
+pub struct ToolchainConfig {
+    /// C compiler driver (e.g. `gcc-13`, `/opt/llvm/bin/clang`).
+    #[serde(default)]
+    pub cc: Option<String>,
+    /// C++ compiler driver, also used to link.
+    #[serde(default)]
+    pub cxx: Option<String>,
+    /// Archiver used for static libraries (`lib.exe` with MSVC).
+    #[serde(default)]
+    pub ar: Option<String>,
+    /// Linker, only used by the MSVC backend (`link.exe`). GCC and Clang link with `cxx`.
+    #[serde(default)]
+    pub link: Option<String>,
+}
+
+/// The programs a GCC-like backend runs, and the flags selecting the target architecture.
+pub struct Toolchain {
+    pub cc: String,
+    /// Arguments given to `cc` before any other, from `CC` (`ccache gcc` runs `ccache` with `gcc`).
+    pub cc_args: Vec<String>,
+    pub cxx: String,
+    /// Arguments given to `cxx` before any other, from `CXX`.
+    pub cxx_args: Vec<String>,
+    pub ar: String,
+    /// Passed to every compile and link command (e.g. `-m32`, `--target=aarch64-linux-gnu`).
+    pub target_flags: Vec<String>,
+}
+
+// A program of the toolchain, with the arguments it is always run with.
+struct Tool {
+    path: String,
+    args: Vec<String>,
+}
+
+// How to look up one program of the toolchain.
+struct ToolLookup<'a> {
+    description: &'static str,
+    config_key: &'static str,
+    configured: Option<&'a str>,
+    env_var: Option<&'static str>,
+    /// The program matching one already chosen (`g++-13` for `gcc-13`), used instead of searching `PATH`.
+    matching: Option<Result<Tool, String>>,
+    names: Vec<String>,
+    versioned: bool,
+}
+
+///
+/// Returns the GNU triple of `arch` on Linux, which is also the prefix of its cross toolchain (`aarch64-linux-gnu-gcc`).
+///
+pub fn gnu_triple(arch: TargetArch) -> &'static str {
+    match arch {
+        TargetArch::X86 => "i686-linux-gnu",
+        TargetArch::X64 => "x86_64-linux-gnu",
+        TargetArch::ARM => "arm-linux-gnueabihf",
+        TargetArch::ARM64 => "aarch64-linux-gnu",
+    }
+}
+
+///
+/// Returns the MinGW-w64 triple of `arch`, which is also the prefix of its toolchain (`x86_64-w64-mingw32-gcc`).
+///
+pub fn mingw_triple(arch: TargetArch) -> &'static str {
+    match arch {
+        TargetArch::X86 => "i686-w64-mingw32",
+        TargetArch::X64 => "x86_64-w64-mingw32",
+        TargetArch::ARM => "armv7-w64-mingw32",
+        TargetArch::ARM64 => "aarch64-w64-mingw32",
+    }
+}
+
+///
+/// Whether the host compiler can target `arch` by itself.
+/// x86 hosts build both x86 and x64 (with `-m32`/`-m64`); ARM hosts only build their own architecture.
+///
+pub fn is_native_arch(arch: TargetArch) -> bool {
+    match env::consts::ARCH {
+        "x86_64" | "x86" => matches!(arch, TargetArch::X86 | TargetArch::X64),
+        "aarch64" => arch == TargetArch::ARM64,
+        "arm" => arch == TargetArch::ARM,
+        _ => false,
+    }
+}
+
+///
+/// Finds the toolchain of a compiler backend for `platform` and `arch`.
+/// Each program is searched, in order, in the solution's `toolchain` block, in its environment variable
+/// (`CC`, `CXX`, `AR`), in `PATH` under its usual names and finally in `PATH` under versioned names
+/// (e.g. `gcc-13`, picking the highest version).
+/// `CC` and `CXX` may hold arguments after the program (`ccache gcc`, `gcc -m32`).
+/// Unless it is set explicitly, the C++ compiler is the one matching the C compiler (`g++-13` next to `gcc-13`).
+/// GCC cross toolchains are only searched in `PATH`, under their triple prefix (`aarch64-linux-gnu-gcc`,
+/// `x86_64-w64-mingw32-gcc` when building for Windows from another platform);
+/// Clang targets other architectures and platforms itself with `--target`.
+/// # Arguments
+/// * `kind` - The compiler backend.
+/// * `platform` - The platform to build for.
+/// * `arch` - The architecture to build for.
+/// * `config` - The solution's `toolchain` block.
+/// * `verbose` - Whether to enable verbose output.
+/// # Returns
+/// * The toolchain, or an error listing everything that was searched, or naming the program that was asked for
+///   and not found.
+///
+pub fn discover_toolchain(kind: CompilerKind, platform: TargetPlatform, arch: TargetArch, config: &ToolchainConfig, verbose: bool) -> Result<Toolchain, String> {
+    let cross_platform = TargetPlatform::host() != Some(platform);
+    let native = is_native_arch(arch) && !cross_platform;
+
+    let (cc_names, cxx_names, ar_names): (&[&str], &[&str], &[&str]) = match kind {
+        // Debian ships MinGW-w64 compilers with a `-posix` or `-win32` threading model suffix only.
+        CompilerKind::Gcc if cross_platform && platform == TargetPlatform::Windows => {
+            (&["gcc", "gcc-posix", "gcc-win32"], &["g++", "g++-posix", "g++-win32"], &["ar"])
+        }
+        CompilerKind::Gcc => (&["gcc", "cc"], &["g++", "c++"], &["ar"]),
+        CompilerKind::Clang => (&["clang"], &["clang++"], &["llvm-ar", "ar"]),
+        CompilerKind::Msvc => return Err("The MSVC toolchain is found by the MSVC backend itself".to_string()),
+    };
+
+    let triple = if platform == TargetPlatform::Windows { mingw_triple(arch) } else { gnu_triple(arch) };
+
+    let mut target_flags: Vec<String> = Vec::new();
+    match arch {
+        TargetArch::X86 if native => target_flags.push("-m32".to_string()),
+        TargetArch::X64 if native => target_flags.push("-m64".to_string()),
+        _ => {}
+    }
+
+    // The solution's toolchain and the environment describe the host compilers.
+    let cross_prefix = if !native && kind == CompilerKind::Gcc {
+        format!("{}-", triple)
+    } else {
+        if !native {
+            target_flags.push(format!("--target={}", triple));
+        }
+        String::new()
+    };
+    let host = cross_prefix.is_empty();
+    let prefixed = |names: &[&str]| names.iter().map(|n| format!("{}{}", cross_prefix, n)).collect::<Vec<String>>();
+
+    let cc = find_tool(ToolLookup {
+        description: "C compiler",
+        config_key: "cc",
+        configured: config.cc.as_deref().filter(|_| host),
+        env_var: host.then_some("CC"),
+        matching: None,
+        names: prefixed(cc_names),
+        versioned: true,
+    }, triple, verbose)?;
+
+    let cxx = find_tool(ToolLookup {
+        description: "C++ compiler",
+        config_key: "cxx",
+        configured: config.cxx.as_deref().filter(|_| host),
+        env_var: host.then_some("CXX"),
+        matching: matching_tool(&cc, &prefixed(cc_names), &prefixed(cxx_names)),
+        names: prefixed(cxx_names),
+        versioned: true,
+    }, triple, verbose)?;
+
+    let ar = find_tool(ToolLookup {
+        description: "archiver",
+        config_key: "ar",
+        configured: config.ar.as_deref().filter(|_| host),
+        env_var: host.then_some("AR"),
+        matching: None,
+        names: prefixed(ar_names),
+        versioned: false,
+    }, triple, verbose)?;
+
+    if !ar.args.is_empty() {
+        return Err(format!("$AR must name a program only, not `{}`", ar.args.join(" ")));
+    }
+
+    Ok(Toolchain { cc: cc.path, cc_args: cc.args, cxx: cxx.path, cxx_args: cxx.args, ar: ar.path, target_flags })
+}
+
+fn find_tool(lookup: ToolLookup, triple: &str, verbose: bool) -> Result<Tool, String> {
+    let mut searched: Vec<String> = Vec::new();
+
+    if let Some(configured) = lookup.configured {
+        match find_program(configured) {
+            Some(path) => return Ok(Tool { path: found(lookup.description, &path, "solution toolchain", verbose), args: Vec::new() }),
+            None => {
+                // An explicit choice that doesn't exist is an error, not something to silently replace.
+                return Err(format!(
+                    "{} `{}` set in the solution's toolchain.{} was not found",
+                    lookup.description, configured, lookup.config_key
+                ));
+            }
+        }
+    }
+
+    if let Some(env_var) = lookup.env_var {
+        searched.push(format!("toolchain.{} (not set)", lookup.config_key));
+
+        match env::var(env_var) {
+            Ok(value) if !value.trim().is_empty() => {
+                let tool = env_tool(value.trim()).ok_or_else(|| {
+                    format!("{} `{}` set in ${} was not found", lookup.description, value.trim(), env_var)
+                })?;
+                found(lookup.description, Path::new(&tool.path), env_var, verbose);
+                return Ok(tool);
+            }
+            _ => searched.push(format!("${} (not set)", env_var)),
+        }
+    }
+
+    if let Some(matching) = lookup.matching {
+        let tool = matching?;
+        found(lookup.description, Path::new(&tool.path), "matching the C compiler", verbose);
+        return Ok(tool);
+    }
+
+    for name in &lookup.names {
+        if let Some(path) = find_in_path(name) {
+            return Ok(Tool { path: found(lookup.description, &path, "PATH", verbose), args: Vec::new() });
+        }
+    }
+    searched.push(format!("PATH for {}", lookup.names.iter().map(|n| format!("`{}`", n)).collect::<Vec<_>>().join(", ")));
+
+    if lookup.versioned {
+        for name in &lookup.names {
+            if let Some(path) = find_versioned_in_path(name) {
+                return Ok(Tool { path: found(lookup.description, &path, "PATH", verbose), args: Vec::new() });
+            }
+        }
+        searched.push(format!("PATH for {}", lookup.names.iter().map(|n| format!("`{}-<version>`", n)).collect::<Vec<_>>().join(", ")));
+    }
+
+    let path_dirs: Vec<String> = path_dirs().iter().map(|d| d.display().to_string()).collect();
+    Err(format!(
+        "No {} found for {}. Searched: {}. PATH directories: [{}]",
+        lookup.description,
+        triple,
+        searched.join("; "),
+        path_dirs.join(", ")
+    ))
+}
+
+// A program set in an environment variable: a path to it (spaces included), or a program followed by its arguments.
+fn env_tool(value: &str) -> Option<Tool> {
+    if let Some(path) = find_program(value) {
+        return Some(Tool { path: path.to_string_lossy().into_owned(), args: Vec::new() });
+    }
+
+    let mut words = split_flags(value).into_iter();
+    let path = find_program(&words.next()?)?;
+    Some(Tool { path: path.to_string_lossy().into_owned(), args: words.collect() })
+}
+
+// The C++ compiler matching the C compiler `cc`: the same command with its compiler (the program or one of the
+// arguments, for launchers like ccache) renamed from `cc_names[i]` to `cxx_names[i]`, keeping any version suffix,
+// and looked up next to it. `None` if `cc` does not run a compiler from `cc_names`.
+fn matching_tool(cc: &Tool, cc_names: &[String], cxx_names: &[String]) -> Option<Result<Tool, String>> {
+    let words = std::iter::once(&cc.path).chain(&cc.args);
+
+    for (index, word) in words.enumerate() {
+        let path = Path::new(word);
+        let Some(file_name) = path.file_name().map(|n| n.to_string_lossy()) else { continue };
+        let stem = file_name.strip_suffix(env::consts::EXE_SUFFIX).unwrap_or(&file_name);
+
+        let Some(cxx_name) = cc_names.iter().zip(cxx_names).find_map(|(cc_name, cxx_name)| {
+            let suffix = stem.strip_prefix(cc_name.as_str())?;
+            (suffix.is_empty() || parse_versioned_name(stem, cc_name).is_some()).then(|| format!("{}{}", cxx_name, suffix))
+        }) else {
+            continue;
+        };
+
+        let cxx_word = path.with_file_name(format!("{}{}", cxx_name, env::consts::EXE_SUFFIX));
+        let cxx_word = if path.components().count() > 1 { cxx_word.to_string_lossy().into_owned() } else { cxx_name.clone() };
+        let Some(cxx_path) = find_program(&cxx_word) else {
+            return Some(Err(format!(
+                "C++ compiler `{}` matching the C compiler `{}` was not found. Set CXX or the solution's toolchain.cxx",
+                cxx_word, word
+            )));
+        };
+
+        let tool = if index == 0 {
+            Tool { path: cxx_path.to_string_lossy().into_owned(), args: cc.args.clone() }
+        } else {
+            let mut args = cc.args.clone();
+            args[index - 1] = cxx_word;
+            Tool { path: cc.path.clone(), args }
+        };
+        return Some(Ok(tool));
+    }
+
+    None
+}
+
+fn found(description: &str, path: &Path, source: &str, verbose: bool) -> String {
+    Console::log_verbose(format!("Using {} {} (from {})", description, path.display(), source).as_str(), verbose);
+    path.to_string_lossy().into_owned()
+}
+
+fn path_dirs() -> Vec<PathBuf> {
+    env::var_os("PATH").map(|p| env::split_paths(&p).collect()).unwrap_or_default()
+}
+
+///
+/// Resolves a program given either as a path (anything containing a separator) or as a name looked up in `PATH`.
+///
+pub fn find_program(name_or_path: &str) -> Option<PathBuf> {
+    let path = Path::new(name_or_path);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    find_in_path(name_or_path)
+}
+
+/// Looks `name` up in the directories of `PATH`.
+pub fn find_in_path(name: &str) -> Option<PathBuf> {
+    let file_name = format!("{}{}", name, env::consts::EXE_SUFFIX);
+    path_dirs()
+        .into_iter()
+        .map(|dir| dir.join(&file_name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Looks up `<name>-<version>` in `PATH` (e.g. `gcc-13`) and returns the highest version found.
+pub fn find_versioned_in_path(name: &str) -> Option<PathBuf> {
+    let mut best: Option<(Vec<u32>, PathBuf)> = None;
+
+    for dir in path_dirs() {
+        let Ok(entries) = read_dir(&dir) else { continue };
+
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let stem = file_name.strip_suffix(env::consts::EXE_SUFFIX).unwrap_or(&file_name);
+
+            if let Some(version) = parse_versioned_name(stem, name)
+                && entry.path().is_file()
+                && best.as_ref().is_none_or(|(v, _)| version > *v) {
+                best = Some((version, entry.path()));
+            }
+        }
+    }
+
+    best.map(|(_, path)| path)
+}
+
+// `gcc-13.2` with base `gcc` gives [13, 2]. Anything else (`gcc-ar`, `gcc-13-foo`) gives None.
+fn parse_versioned_name(file_name: &str, base: &str) -> Option<Vec<u32>> {
+    let version = file_name.strip_prefix(base)?.strip_prefix('-')?;
+    version.split('.').map(|part| part.parse::<u32>().ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{env_tool, matching_tool, parse_versioned_name, Tool};
+
+    #[test]
+    fn versioned_names() {
+        assert_eq!(parse_versioned_name("gcc-13", "gcc"), Some(vec![13]));
+        assert_eq!(parse_versioned_name("g++-12.3", "g++"), Some(vec![12, 3]));
+        assert_eq!(parse_versioned_name("clang-18", "clang"), Some(vec![18]));
+    }
+
+    #[test]
+    fn not_versioned_names() {
+        assert_eq!(parse_versioned_name("gcc", "gcc"), None);
+        assert_eq!(parse_versioned_name("gcc-ar", "gcc"), None);
+        assert_eq!(parse_versioned_name("gcc-ar-13", "gcc"), None);
+        assert_eq!(parse_versioned_name("clang++-18", "clang"), None);
+    }
+
+    // Tools of a scratch directory, which are looked up by path so `PATH` does not matter.
+    #[cfg(unix)]
+    fn tools(test: &str, names: &[&str]) -> std::path::PathBuf {
+        let dir = crate::test_fixtures::temp_dir(test);
+        for name in names {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        dir
+    }
+
+    #[cfg(unix)]
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_cxx_compiler_matches_the_version_of_the_cc_compiler() {
+        let dir = tools("matching_cxx", &["gcc-13", "g++-13", "gcc-12", "g++-14", "ccache", "clang-18", "clang++-18", "mycc"]);
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        let tool = |name: &str, args: &[&str]| Tool { path: path(name), args: names(args) };
+        let gcc = (names(&["gcc", "cc"]), names(&["g++", "c++"]));
+        let clang = (names(&["clang"]), names(&["clang++"]));
+
+        let cxx = matching_tool(&tool("gcc-13", &[]), &gcc.0, &gcc.1).unwrap().unwrap();
+        assert_eq!((cxx.path, cxx.args), (path("g++-13"), Vec::new()));
+
+        // A launcher keeps running the matching compiler, with the same arguments
+        let cxx = matching_tool(&tool("ccache", &[&path("gcc-13"), "-m32"]), &gcc.0, &gcc.1).unwrap().unwrap();
+        assert_eq!((cxx.path, cxx.args), (path("ccache"), vec![path("g++-13"), "-m32".to_string()]));
+
+        let cxx = matching_tool(&tool("clang-18", &[]), &clang.0, &clang.1).unwrap().unwrap();
+        assert_eq!(cxx.path, path("clang++-18"));
+
+        // Another version is not a match
+        assert!(matching_tool(&tool("gcc-12", &[]), &gcc.0, &gcc.1).unwrap().is_err());
+        // Nor is a compiler it does not know
+        assert!(matching_tool(&tool("mycc", &[]), &gcc.0, &gcc.1).is_none());
+        assert!(matching_tool(&tool("ccache", &["mycc"]), &gcc.0, &gcc.1).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn environment_tools_are_split_into_program_and_arguments() {
+        let dir = tools("env_tool", &["ccache", "gcc"]);
+        std::fs::create_dir_all(dir.join("my tools")).unwrap();
+        std::fs::write(dir.join("my tools/gcc"), "").unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+
+        let tool = env_tool(&format!("{} gcc -m32", path("ccache"))).unwrap();
+        assert_eq!((tool.path, tool.args), (path("ccache"), names(&["gcc", "-m32"])));
+        // A path with spaces needs no quotes, but may have them
+        assert_eq!(env_tool(&path("my tools/gcc")).unwrap().path, path("my tools/gcc"));
+        let tool = env_tool(&format!("'{}' -m32", path("my tools/gcc"))).unwrap();
+        assert_eq!((tool.path, tool.args), (path("my tools/gcc"), names(&["-m32"])));
+        assert!(env_tool(&format!("{} gcc", path("sccache"))).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,14 +1,15 @@
 use std::path::Path;
 use std::fs::read_to_string;
 
-use crate::solution::Solution;
+use crate::helpers::version_tools::{Version, VersionReq};
+use crate::solution::{ProjectType, Solution};
 
 pub fn parse_config(path: &Path) -> Result<Solution, String> {
 
     let unserialized_string = read_to_string(path);
 
     // Error handling
-    if (&unserialized_string).is_err() {
+    if unserialized_string.is_err() {
         return Err(format!("Failed to read config file: {}", path.display()));
     }
     
@@ -19,5 +20,27 @@ pub fn parse_config(path: &Path) -> Result<Solution, String> {
     let solution: Solution = serde_json::from_str(&contents)
         .map_err(|e| format!("Failed to parse config file `{}': {}", path.display(), e))?;
 
+    // Profile names become output directories.
+    for name in solution.profiles.keys() {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("Invalid profile name `{}': only letters, digits, `-' and `_' are allowed", name));
+        }
+    }
+
+    // Versions are compared when resolving dependencies, where a malformed one would just never match.
+    for project in &solution.projects {
+        if project.test && project.project_type != ProjectType::Executable {
+            return Err(format!("Project {} is a test, so it must be an Executable", project.name));
+        }
+
+        project.version.parse::<Version>()
+            .map_err(|e| format!("Invalid version of project {}: {}", project.name, e))?;
+
+        for dep in &project.dependencies {
+            dep.version.parse::<VersionReq>()
+                .map_err(|e| format!("Invalid version requirement for dependency {} of project {}: {}", dep.name, project.name, e))?;
+        }
+    }
+
     Ok(solution)
 }
@@ -0,0 +1,419 @@
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+use crate::compiler_interfaces::common::{CompileCommand, Compiler, Compilers};
+use crate::dependency_manager::local_resolve::{resolve_project_build_inputs, ProjectBuildInputs};
+use crate::helpers::console::Console;
+use crate::helpers::job_pool::JobPool;
+use crate::solution::{Project, Solution, TargetArch, TargetPlatform};
+
+/// A project of the solution built for one of its architectures, along with everything needed to build it.
+pub struct BuildNode {
+    pub project: Project,
+    pub arch: TargetArch,
+    pub inputs: ProjectBuildInputs,
+    /// Indices (in `BuildGraph::nodes`) of the local projects, for the same architecture, that must be linked before this one.
+    pub dependencies: Vec<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum NodeState {
+    Pending,
+    Done,
+    Failed,
+}
+
+///
+/// Dependency graph of every project in the solution.
+/// Each project is a node per architecture in its `target_archs`; edges come from `resolve_local_dependencies_in_order`
+/// and only link nodes of the same architecture.
+///
+pub struct BuildGraph {
+    pub nodes: Vec<BuildNode>,
+}
+
+impl BuildGraph {
+    ///
+    /// Builds the graph of the `selected` projects of `solution` that target `platform`.
+    /// A project is skipped, with a warning, for the architectures one of its dependencies is not built for.
+    /// Circular dependencies must have been rejected beforehand.
+    /// # Arguments
+    /// * `solution` - The solution containing all projects.
+    /// * `solution_root` - The root path of the solution.
+    /// * `platform` - The platform being built for.
+    /// * `selected` - Names of the projects to build, which must include their dependencies (see `select_projects`).
+    /// * `verbose` - Whether to enable verbose output.
+    /// # Returns
+    /// * The graph, or an error if the build inputs of a project cannot be resolved
+    ///   or if a dependency does not target the platform of its dependent.
+    ///
+    pub fn from_solution(solution: &Solution, solution_root: &Path, platform: TargetPlatform, selected: &[String], verbose: bool) -> Result<BuildGraph, &'static str> {
+        let mut nodes: Vec<BuildNode> = Vec::new();
+
+        for project in &solution.projects {
+            if !selected.contains(&project.name) {
+                Console::log_verbose(format!("Skipping project {}: it is not selected", project.name).as_str(), verbose);
+                continue;
+            }
+
+            if !project.targets_platform(platform) {
+                Console::log_info(format!("Skipping project {}: it does not target {}", project.name, platform.name()).as_str());
+                continue;
+            }
+
+            if project.target_archs.is_empty() {
+                Console::log_warning(format!("Project {} has no target_archs; it will not be built", project.name).as_str());
+                continue;
+            }
+
+            let inputs = resolve_project_build_inputs(project, solution, solution_root, verbose)?;
+
+            if let Some(dep) = inputs.local_deps_in_order.iter().find(|d| !d.targets_platform(platform)) {
+                Console::log_fatal(format!(
+                    "Project {} targets {} but its dependency {} does not (add it to the dependency's target_platforms)",
+                    project.name,
+                    platform.name(),
+                    dep.name
+                ).as_str());
+                return Err("Dependency does not target the required platform");
+            }
+
+            for arch in &project.target_archs {
+                if nodes.iter().any(|n| n.project.name == project.name && n.arch == *arch) {
+                    continue; // Architecture listed twice
+                }
+
+                nodes.push(BuildNode {
+                    project: project.clone(),
+                    arch: *arch,
+                    inputs: inputs.clone(),
+                    dependencies: Vec::new(),
+                });
+            }
+        }
+
+        // Dependencies are resolved once every node exists, since projects may be declared in any order.
+        // Skipping a node may leave its dependents without a dependency, so they are skipped in turn.
+        while let Some((index, dep)) = nodes.iter().enumerate().find_map(|(index, node)| {
+            node.inputs.local_deps_in_order
+                .iter()
+                .find(|dep| !nodes.iter().any(|n| n.project.name == dep.name && n.arch == node.arch))
+                .map(|dep| (index, dep.name.clone()))
+        }) {
+            let node = nodes.remove(index);
+            Console::log_warning(format!(
+                "Skipping project {} for {}: its dependency {} is not built for it (add it to the dependency's target_archs)",
+                node.project.name,
+                node.arch.name(),
+                dep
+            ).as_str());
+        }
+
+        for index in 0..nodes.len() {
+            nodes[index].dependencies = nodes[index].inputs.local_deps_in_order
+                .iter()
+                .map(|dep| {
+                    nodes.iter()
+                        .position(|n| n.project.name == dep.name && n.arch == nodes[index].arch)
+                        .expect("Nodes without their dependencies were skipped")
+                })
+                .collect();
+        }
+
+        Ok(BuildGraph { nodes })
+    }
+
+    /// Every architecture at least one node is built for.
+    pub fn archs(&self) -> Vec<TargetArch> {
+        let mut archs: Vec<TargetArch> = Vec::new();
+        for node in &self.nodes {
+            if !archs.contains(&node.arch) {
+                archs.push(node.arch);
+            }
+        }
+        archs
+    }
+
+    ///
+    /// Crafts the compile command of every source file of every node, as `build` would run them, without compiling anything.
+    /// # Arguments
+    /// * `compilers` - The compiler backend to build with, for each architecture of the graph.
+    /// * `solution_root` - The root path of the solution.
+    /// # Returns
+    /// * The commands of every node, in graph order, otherwise an error message.
+    ///
+    pub fn compile_commands(&self, compilers: &Compilers, solution_root: &Path) -> Result<Vec<CompileCommand>, &'static str> {
+        let mut commands = Vec::new();
+        for node in &self.nodes {
+            let compiler = compilers.get(&node.arch).ok_or("No compiler available for an architecture of the graph")?;
+            commands.extend(compiler.compile_commands(&node.project, solution_root, &node.inputs.include_dirs)?);
+        }
+        Ok(commands)
+    }
+
+    ///
+    /// Builds every project of the graph.
+    /// All projects compile concurrently (the job pool bounds how many processes actually run);
+    /// a project links as soon as every project it depends on has been linked.
+    /// # Arguments
+    /// * `compilers` - The compiler backend to build with, for each architecture of the graph.
+    /// * `solution` - The solution containing all projects.
+    /// * `solution_root` - The root path of the solution.
+    /// * `job_pool` - The job pool shared with the compilers.
+    /// * `verbose` - Whether to enable verbose output.
+    /// # Returns
+    /// * `Ok(())` if every project was built, otherwise an error message.
+    ///
+    pub fn build(
+        &self,
+        compilers: &Compilers,
+        solution: &Solution,
+        solution_root: &Path,
+        job_pool: &JobPool,
+        verbose: bool,
+    ) -> Result<(), &'static str> {
+        let states = Mutex::new(vec![NodeState::Pending; self.nodes.len()]);
+        let state_changed = Condvar::new();
+
+        thread::scope(|scope| {
+            for (index, node) in self.nodes.iter().enumerate() {
+                let states = &states;
+                let state_changed = &state_changed;
+
+                scope.spawn(move || {
+                    let built = match compilers.get(&node.arch) {
+                        Some(compiler) => self.build_node(node, states, state_changed, compiler.as_ref(), solution, solution_root, job_pool, verbose),
+                        None => {
+                            Console::log_fatal(format!("No compiler available for {}", node.arch.name()).as_str());
+                            false
+                        }
+                    };
+
+                    if !built {
+                        job_pool.mark_failed();
+                    }
+
+                    let mut states = states.lock().unwrap();
+                    states[index] = if built { NodeState::Done } else { NodeState::Failed };
+                    state_changed.notify_all();
+                });
+            }
+        });
+
+        let failed: Vec<String> = self
+            .nodes
+            .iter()
+            .zip(states.into_inner().unwrap())
+            .filter(|(_, state)| *state != NodeState::Done)
+            .map(|(node, _)| format!("{} ({})", node.project.name, node.arch.name()))
+            .collect();
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Console::log_error(&format!("The following projects were not built: {}", failed.join(", ")));
+            Err("Build failed.")
+        }
+    }
+
+    // Compiles a node, waits for its dependencies and links it. Returns whether the node was built.
+    #[allow(clippy::too_many_arguments)]
+    fn build_node(
+        &self,
+        node: &BuildNode,
+        states: &Mutex<Vec<NodeState>>,
+        state_changed: &Condvar,
+        compiler: &(dyn Compiler + Sync),
+        solution: &Solution,
+        solution_root: &Path,
+        job_pool: &JobPool,
+        verbose: bool,
+    ) -> bool {
+        let project = &node.project;
+
+        if job_pool.should_stop() {
+            return false;
+        }
+
+        // Compiling only needs the dependencies' sources, which already exist.
+        if let Err(e) = compiler.compile_project(project, solution, solution_root, node.inputs.include_dirs.clone(), verbose) {
+            Console::log_fatal(format!("Error compiling project {} ({}): {}", project.name, node.arch.name(), e).as_str());
+            return false;
+        }
+        Console::log_success(format!("=== Project {} ({}) compiled successfully ===", project.name, node.arch.name()).as_str());
+
+        // Linking needs the dependencies' artifacts.
+        {
+            let mut states = states.lock().unwrap();
+            loop {
+                if let Some(&failed_dep) = node.dependencies.iter().find(|&&d| states[d] == NodeState::Failed) {
+                    Console::log_error(format!(
+                        "Not linking project {} ({}): dependency {} failed to build",
+                        project.name, node.arch.name(), self.nodes[failed_dep].project.name
+                    ).as_str());
+                    return false;
+                }
+
+                if node.dependencies.iter().all(|&d| states[d] == NodeState::Done) {
+                    break;
+                }
+
+                states = state_changed.wait(states).unwrap();
+            }
+        }
+
+        if job_pool.should_stop() {
+            return false;
+        }
+
+        // Link current project.
+        let _slot = job_pool.acquire();
+        if let Err(e) = compiler.link_project(project, solution, solution_root, &node.inputs.local_deps_in_order, verbose) {
+            Console::log_fatal(format!("Error linking project {} ({}): {}\n", project.name, node.arch.name(), e).as_str());
+            return false;
+        }
+        Console::log_success(format!("=== Project {} ({}) linked successfully ===", project.name, node.arch.name()).as_str());
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+    use crate::compiler_interfaces::common::{CompileCommand, Compiler, Compilers};
+    use crate::helpers::job_pool::JobPool;
+    use crate::solution::{Project, ProjectType, Solution, TargetArch, TargetPlatform};
+    use crate::test_fixtures::{project, solution, temp_dir};
+    use super::BuildGraph;
+
+    // Fails to compile the projects named in `failing` and records the projects it links.
+    struct StandInCompiler {
+        failing: Vec<&'static str>,
+        linked: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Compiler for StandInCompiler {
+        fn compile_file(&self, _: &Project, _: &Path, _: &Path, _: &[PathBuf], _: bool) -> Result<(), &'static str> {
+            Ok(())
+        }
+
+        fn compile_project(&self, project: &Project, _: &Solution, _: &Path, _: Vec<PathBuf>, _: bool) -> Result<(), &'static str> {
+            if self.failing.contains(&project.name.as_str()) { Err("Compilation failed") } else { Ok(()) }
+        }
+
+        fn compile_commands(&self, _: &Project, _: &Path, _: &[PathBuf]) -> Result<Vec<CompileCommand>, &'static str> {
+            Ok(Vec::new())
+        }
+
+        fn link_project(&self, project: &Project, _: &Solution, _: &Path, _: &[Project], _: bool) -> Result<(), &'static str> {
+            self.linked.lock().unwrap().push(project.name.clone());
+            Ok(())
+        }
+
+        fn detect_compiler_path() -> Option<String> {
+            None
+        }
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    // A scratch solution root holding a directory per project.
+    fn solution_root(test: &str, projects: &[String]) -> PathBuf {
+        let root = temp_dir(test);
+        for name in projects {
+            std::fs::create_dir_all(root.join(name)).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn nodes_only_depend_on_nodes_of_their_architecture() {
+        let mut app = project("app", ProjectType::Executable, &["lib"]);
+        app.target_archs = vec![TargetArch::X64, TargetArch::X86];
+        let mut lib = project("lib", ProjectType::StaticLib, &[]);
+        lib.target_archs = vec![TargetArch::X86, TargetArch::X64, TargetArch::X86];
+        let sol = solution(vec![app, lib]);
+        let selected = names(&["lib", "app"]);
+        let root = solution_root("arch_edges", &selected);
+
+        let graph = BuildGraph::from_solution(&sol, &root, TargetPlatform::Linux, &selected, false).unwrap();
+        assert_eq!(graph.nodes.len(), 4);
+        for node in graph.nodes.iter().filter(|n| n.project.name == "app") {
+            let [dep] = node.dependencies[..] else { panic!("app should have one dependency") };
+            assert_eq!(graph.nodes[dep].project.name, "lib");
+            assert_eq!(graph.nodes[dep].arch, node.arch);
+        }
+        assert_eq!(graph.archs(), [TargetArch::X64, TargetArch::X86]);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn dependencies_must_target_the_platform_of_their_dependents() {
+        let app = project("app", ProjectType::Executable, &["lib"]);
+        let mut lib = project("lib", ProjectType::StaticLib, &[]);
+        lib.target_platforms = vec![TargetPlatform::Windows];
+        let sol = solution(vec![app, lib]);
+        let root = solution_root("platforms", &names(&["app", "lib"]));
+
+        assert!(BuildGraph::from_solution(&sol, &root, TargetPlatform::Linux, &names(&["lib", "app"]), false).is_err());
+        // Alone, the project is skipped rather than rejected
+        let graph = BuildGraph::from_solution(&sol, &root, TargetPlatform::Linux, &names(&["lib"]), false).unwrap();
+        assert!(graph.nodes.is_empty());
+        let graph = BuildGraph::from_solution(&sol, &root, TargetPlatform::Windows, &names(&["lib"]), false).unwrap();
+        assert_eq!(graph.nodes.len(), 1);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn a_failed_dependency_keeps_its_dependents_from_linking() {
+        // app -> net -> core, tool; core fails to compile
+        let app = project("app", ProjectType::Executable, &["net"]);
+        let net = project("net", ProjectType::StaticLib, &["core"]);
+        let core = project("core", ProjectType::StaticLib, &[]);
+        let tool = project("tool", ProjectType::Executable, &[]);
+        let sol = solution(vec![app, net, core, tool]);
+        let selected = names(&["core", "net", "app", "tool"]);
+        let root = solution_root("failed_dependency", &selected);
+
+        let graph = BuildGraph::from_solution(&sol, &root, TargetPlatform::Linux, &selected, false).unwrap();
+        let linked = Arc::new(Mutex::new(Vec::new()));
+        let mut compilers: Compilers = HashMap::new();
+        compilers.insert(TargetArch::X64, Box::new(StandInCompiler { failing: vec!["core"], linked: linked.clone() }));
+
+        // Keep going so the independent project still builds
+        let job_pool = JobPool::new(2, true);
+        assert!(graph.build(&compilers, &sol, &root, &job_pool, false).is_err());
+        assert_eq!(*linked.lock().unwrap(), ["tool"]);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn projects_are_skipped_for_the_archs_their_dependencies_lack() {
+        // app (X64, X86) -> lib (X64), tool (X86) -> app
+        let mut app = project("app", ProjectType::StaticLib, &["lib"]);
+        app.target_archs = vec![TargetArch::X64, TargetArch::X86];
+        let lib = project("lib", ProjectType::StaticLib, &[]);
+        let mut tool = project("tool", ProjectType::Executable, &["app"]);
+        tool.target_archs = vec![TargetArch::X86];
+        let sol = solution(vec![tool, app, lib]);
+        let selected = names(&["lib", "app", "tool"]);
+        let root = solution_root("skipped_archs", &selected);
+
+        let graph = BuildGraph::from_solution(&sol, &root, TargetPlatform::Linux, &selected, false).unwrap();
+        let nodes: Vec<(&str, TargetArch)> = graph.nodes.iter().map(|n| (n.project.name.as_str(), n.arch)).collect();
+        assert_eq!(nodes, [("app", TargetArch::X64), ("lib", TargetArch::X64)]);
+        assert_eq!(graph.nodes[0].dependencies, [1]);
+        assert_eq!(graph.archs(), [TargetArch::X64]);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
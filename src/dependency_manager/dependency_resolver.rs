@@ -1,6 +1,5 @@
-use std::fs::create_dir_all;
-use std::path::PathBuf;
-use crate::solution::{Project, Solution};
+use std::path::{Path, PathBuf};
+use crate::solution::{Dependency, Project, ProjectType, Solution};
 use crate::Console;
 use crate::helpers::version_tools::version_check;
 
@@ -22,7 +21,7 @@ pub fn find_local_dependencies(project: &Project, solution: &Solution, verbose:
 
         // Checks each project for a matching name and version
         solution.projects.iter().for_each(|p| {
-            if &project.name == &p.name {
+            if project.name == p.name {
                 // Skip self
                 return;
             }
@@ -51,32 +50,207 @@ pub fn find_local_dependencies(project: &Project, solution: &Solution, verbose:
 }
 
 ///
-/// Checks for circular dependencies in the project graph.
-/// Uses Recursion to traverse dependencies.
+/// Returns the preprocessor definition telling a project that one of its optional dependencies is available:
+/// `HAS_` followed by the dependency name in uppercase, anything but letters and digits becoming `_` (`gtk+-3.0` gives `HAS_GTK__3_0`).
+///
+pub fn optional_dependency_define(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    format!("HAS_{}", name)
+}
+
+///
+/// Reports a dependency that could not be resolved: an error if it is required, a warning if it is optional.
 /// # Arguments
-/// * `project` - The project to check for circular dependencies.
-/// * `solution` - The solution containing all projects.
-/// * `visited` - A mutable vector to keep track of visited projects during the check.
+/// * `project` - The project declaring the dependency.
+/// * `dep` - The dependency.
+/// * `reason` - Why it could not be resolved.
+/// * `rejected` - The candidates that were found but did not satisfy the wanted version (e.g. `zlib 1.2.13 (pkg-config)`).
 /// # Returns
-/// * A boolean indicating whether a circular dependency exists.
+/// * An error if the dependency is required.
 ///
-pub fn has_circular_dependency(project: &Project, solution: &Solution, visited: &mut Vec<String>) -> bool {
-    if visited.contains(&project.name) {
-        return true; // Circular dependency detected
+pub fn report_unresolved_dependency(project: &Project, dep: &Dependency, reason: &str, rejected: &[String]) -> Result<(), &'static str> {
+    let mut details = format!("{} {} of project {}: {}", dep.name, dep.version, project.name, reason);
+    if !rejected.is_empty() {
+        details.push_str(format!(" (rejected: {})", rejected.join(", ")).as_str());
     }
 
-    visited.push(project.name.clone());
+    if dep.optional {
+        Console::log_warning(format!("Skipping optional dependency {}", details).as_str());
+        Ok(())
+    } else {
+        Console::log_fatal(format!("Missing required dependency {}", details).as_str());
+        Err("A required dependency could not be resolved")
+    }
+}
 
-    for dep in &project.dependencies {
-        if let Some(dep_project) = solution.projects.iter().find(|p| p.name == dep.name) {
-            if has_circular_dependency(dep_project, solution, visited) {
-                return true;
+///
+/// Checks, in a single pass over the solution, the dependencies of every project on other projects of the solution:
+/// each must have a matching version, and they must not form cycles.
+/// Optional dependencies that are satisfied add `HAS_<NAME>` to the project's defines; unsatisfied ones are skipped.
+/// Dependencies that are not projects of the solution are left to `resolve_external_dependencies`.
+/// # Arguments
+/// * `solution` - The solution; the defines of its projects are updated in place.
+/// * `verbose` - Whether to enable verbose output.
+/// # Returns
+/// * An error if a required dependency is not satisfied by any project of the solution or if there is a cycle,
+///   once everything has been reported (each cycle as a chain such as `app -> net -> util -> app`).
+///
+pub fn check_local_dependencies(solution: &mut Solution, verbose: bool) -> Result<(), &'static str> {
+    let mut result = Ok(());
+    let mut defines: Vec<(usize, String)> = Vec::new();
+    // Indices of the projects each project's dependencies resolve to.
+    let mut edges: Vec<Vec<usize>> = vec![Vec::new(); solution.projects.len()];
+
+    for (index, project) in solution.projects.iter().enumerate() {
+        for dep in &project.dependencies {
+            let candidates: Vec<usize> = (0..solution.projects.len()).filter(|&i| solution.projects[i].name == dep.name).collect();
+            if candidates.is_empty() {
+                continue; // External
+            }
+
+            if let Some(&target) = candidates.iter().find(|&&i| version_check(&dep.version, &solution.projects[i].version)) {
+                Console::log_verbose(format!("Dependency {} {} of project {} is satisfied", dep.name, dep.version, project.name).as_str(), verbose);
+                edges[index].push(target);
+                if dep.optional {
+                    defines.push((index, optional_dependency_define(&dep.name)));
+                }
+                continue;
+            }
+
+            let rejected: Vec<String> = candidates.iter().map(|&i| format!("{} {}", dep.name, solution.projects[i].version)).collect();
+            if report_unresolved_dependency(project, dep, "no project of the solution has a matching version", &rejected).is_err() {
+                result = Err("A required dependency could not be resolved");
+            }
+        }
+    }
+
+    for cycle in find_cycles(&edges) {
+        let chain: Vec<&str> = cycle.iter().map(|&i| solution.projects[i].name.as_str()).collect();
+        Console::log_fatal(format!("Circular dependency: {}", chain.join(" -> ")).as_str());
+        result = Err("Circular dependency detected");
+    }
+
+    for (index, define) in defines {
+        solution.projects[index].defines.push(define);
+    }
+
+    result
+}
+
+///
+/// Finds the elementary cycles of a graph with Johnson's algorithm: for each node, from the smallest, the cycles through
+/// it are searched within its strongly connected component among the larger nodes, so each is found exactly once.
+/// # Arguments
+/// * `edges` - The nodes each node points to.
+/// # Returns
+/// * The cycles, each starting and ending with its smallest node (`[0, 1, 0]`), without duplicates.
+///
+pub fn find_cycles(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    struct Search<'a> {
+        edges: &'a [Vec<usize>],
+        start: usize,
+        component: Vec<bool>,
+        blocked: Vec<bool>,
+        blocked_by: Vec<Vec<usize>>,
+        path: Vec<usize>,
+        cycles: Vec<Vec<usize>>,
+    }
+
+    // Nodes reachable from `start` through nodes not smaller than it.
+    fn reachable(start: usize, edges: &[Vec<usize>]) -> Vec<bool> {
+        let mut seen = vec![false; edges.len()];
+        let mut stack = vec![start];
+        while let Some(node) = stack.pop() {
+            for &next in &edges[node] {
+                if next >= start && !seen[next] {
+                    seen[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        seen
+    }
+
+    // Whether a cycle through `search.start` goes on from `node`. Nodes that lead to none stay blocked until one
+    // of the nodes they lead to is unblocked.
+    fn circuit(node: usize, search: &mut Search) -> bool {
+        let mut found = false;
+        search.path.push(node);
+        search.blocked[node] = true;
+
+        for &next in &search.edges[node] {
+            if !search.component[next] {
+                continue;
+            }
+            if next == search.start {
+                let mut cycle = search.path.clone();
+                cycle.push(next);
+                if !search.cycles.contains(&cycle) {
+                    search.cycles.push(cycle);
+                }
+                found = true;
+            } else if !search.blocked[next] && circuit(next, search) {
+                found = true;
+            }
+        }
+
+        if found {
+            unblock(node, search);
+        } else {
+            for &next in &search.edges[node] {
+                if search.component[next] && !search.blocked_by[next].contains(&node) {
+                    search.blocked_by[next].push(node);
+                }
+            }
+        }
+
+        search.path.pop();
+        found
+    }
+
+    fn unblock(node: usize, search: &mut Search) {
+        search.blocked[node] = false;
+        for waiting in std::mem::take(&mut search.blocked_by[node]) {
+            if search.blocked[waiting] {
+                unblock(waiting, search);
             }
         }
     }
 
-    visited.pop();
-    false
+    let mut reverse = vec![Vec::new(); edges.len()];
+    for (node, targets) in edges.iter().enumerate() {
+        for &target in targets {
+            reverse[target].push(node);
+        }
+    }
+
+    let mut search = Search {
+        edges,
+        start: 0,
+        component: Vec::new(),
+        blocked: vec![false; edges.len()],
+        blocked_by: vec![Vec::new(); edges.len()],
+        path: Vec::new(),
+        cycles: Vec::new(),
+    };
+    for start in 0..edges.len() {
+        // The strongly connected component of `start` among the nodes not smaller than it.
+        let forward = reachable(start, edges);
+        let backward = reachable(start, &reverse);
+        search.start = start;
+        search.component = forward.iter().zip(&backward).map(|(f, b)| *f && *b).collect();
+        if !search.component[start] {
+            continue;
+        }
+
+        search.blocked.fill(false);
+        search.blocked_by.iter_mut().for_each(Vec::clear);
+        circuit(start, &mut search);
+    }
+    search.cycles
 }
 
 pub fn find_headers_in_folder(folder: PathBuf) -> Vec<PathBuf> {
@@ -88,10 +262,9 @@ pub fn find_headers_in_folder(folder: PathBuf) -> Vec<PathBuf> {
     match files {
         Ok(file_list) => {
             for file in file_list {
-                if let Some(ext) = file.extension() {
-                    if ext == "h" || ext == "hpp" || ext == "hh" {
-                        header_paths.push(folder.join(&file));
-                    }
+                if let Some(ext) = file.extension()
+                    && (ext == "h" || ext == "hpp" || ext == "hh") {
+                    header_paths.push(folder.join(&file));
                 }
             }
         },
@@ -112,36 +285,28 @@ pub struct ProjectBuildInputs {
     /// Include directories to pass to the compiler when compiling `project`.
     /// These are absolute or solution-root-relative paths (caller decides how to interpret).
     pub include_dirs: Vec<PathBuf>,
-    /// Directories that contain linkable outputs for local deps (currently: `.o` files).
-    ///
-    /// For a dependency at `<solution_root>/<dep.path>`, this is typically:
-    /// `<solution_root>/output/<dep.path>`.
-    pub dep_output_dirs: Vec<PathBuf>,
 }
 
 /// Resolves local (in-solution) dependencies in a dependency-first order.
 ///
 /// Notes:
 /// - This is transitive: if A depends on B and B depends on C, the result for A is [C, B].
-/// - Missing local deps are ignored here (they may be external/system deps).
+/// - Dependencies without a matching project are ignored here (they may be external, or optional).
 pub fn resolve_local_dependencies_in_order(
     project: &Project,
     solution: &Solution,
 ) -> Vec<Project> {
     fn visit(current: &Project, solution: &Solution, out: &mut Vec<Project>, visiting: &mut Vec<String>) {
         if visiting.contains(&current.name) {
-            // Circular dependency should be checked earlier via has_circular_dependency.
+            // Circular dependencies are rejected earlier by check_local_dependencies.
             return;
         }
         visiting.push(current.name.clone());
 
         for dep in &current.dependencies {
-            if let Some(dep_project) = solution.projects.iter().find(|p| p.name == dep.name) {
-                // Best-effort version check; if it doesn't match, treat as non-local.
-                if !version_check(&dep.version, &dep_project.version) {
-                    continue;
-                }
-
+            // Unsatisfied required dependencies are rejected earlier by check_local_dependencies;
+            // unsatisfied optional ones are skipped.
+            if let Some(dep_project) = solution.projects.iter().find(|p| p.name == dep.name && version_check(&dep.version, &p.version)) {
                 visit(dep_project, solution, out, visiting);
 
                 if !out.iter().any(|p| p.name == dep_project.name) {
@@ -159,6 +324,55 @@ pub fn resolve_local_dependencies_in_order(
     out
 }
 
+///
+/// Checks that every name in `names` is a project of `solution`, reporting the first one that is not.
+///
+pub fn check_project_names<'a>(solution: &Solution, mut names: impl Iterator<Item = &'a String>) -> Result<(), &'static str> {
+    if let Some(unknown) = names.find(|name| !solution.projects.iter().any(|p| p.name == **name)) {
+        let names: Vec<&str> = solution.projects.iter().map(|p| p.name.as_str()).collect();
+        Console::log_fatal(format!("Unknown project: {}. The projects of the solution are: {}", unknown, names.join(", ")).as_str());
+        return Err("Unknown project");
+    }
+    Ok(())
+}
+
+///
+/// Selects the projects to build: the ones named in `projects` (every project if empty) except the ones in `exclude`,
+/// along with their local dependencies, transitively.
+/// # Arguments
+/// * `solution` - The solution containing all projects.
+/// * `projects` - Names of the projects to build.
+/// * `exclude` - Names of the projects not to build.
+/// # Returns
+/// * The names of the projects to build, dependencies before dependents, or an error if a name is not a project
+///   of the solution or if an excluded project is needed by a selected one.
+///
+pub fn select_projects(solution: &Solution, projects: &[String], exclude: &[String]) -> Result<Vec<String>, &'static str> {
+    check_project_names(solution, projects.iter().chain(exclude))?;
+
+    let mut selected: Vec<String> = Vec::new();
+    let roots = solution.projects
+        .iter()
+        .filter(|p| (projects.is_empty() || projects.contains(&p.name)) && !exclude.contains(&p.name));
+
+    for root in roots {
+        for dep in resolve_local_dependencies_in_order(root, solution) {
+            if exclude.contains(&dep.name) {
+                Console::log_fatal(format!("Project {} is excluded, but {} depends on it", dep.name, root.name).as_str());
+                return Err("An excluded project is needed");
+            }
+            if !selected.contains(&dep.name) {
+                selected.push(dep.name);
+            }
+        }
+        if !selected.contains(&root.name) {
+            selected.push(root.name.clone());
+        }
+    }
+
+    Ok(selected)
+}
+
 /// Computes include directories for `project`.
 ///
 /// - Validates that `project.additional_includes` exist relative to `<solution_root>/<project.path>`.
@@ -166,13 +380,12 @@ pub fn resolve_local_dependencies_in_order(
 pub fn resolve_project_build_inputs(
     project: &Project,
     solution: &Solution,
-    solution_root: &PathBuf,
+    solution_root: &Path,
     verbose: bool,
 ) -> Result<ProjectBuildInputs, &'static str> {
     let local_deps_in_order = resolve_local_dependencies_in_order(project, solution);
 
     let mut include_dirs: Vec<PathBuf> = Vec::new();
-    let mut dep_output_dirs: Vec<PathBuf> = Vec::new();
 
     // Project additional include dirs
     for inc in &project.additional_includes {
@@ -192,7 +405,7 @@ pub fn resolve_project_build_inputs(
         include_dirs.push(abs_inc_path);
     }
 
-    // Local dependency roots as include dirs + dependency output dirs for linking
+    // Local dependency roots as include dirs
     for dep in &local_deps_in_order {
         let dep_root = solution_root
             .join(&dep.path)
@@ -203,36 +416,217 @@ pub fn resolve_project_build_inputs(
             Console::log_fatal("Failed to locate dependency source directory");
         }
         include_dirs.push(dep_root.unwrap());
+    }
 
-        // Dependency objects are placed in `<solution_root>/output/<dep.path>` by the compiler backend.
-        // Canonicalize so link inputs are absolute.
-        let abs_dep_output_dir = solution_root
-            .join("output")
-            .join(&dep.path)
-            .canonicalize();
-
-        if abs_dep_output_dir.is_err() {
-            create_dir_all(
-                solution_root
-                    .join("output")
-                    .join(&dep.path)
-            )
-                .map_err(|_| "Failed to create dependency output directory")?;
+    // Library directories and prebuilt libraries are not produced by the build: they must already exist.
+    for (kind, relative) in project.library_dirs.iter().map(|d| ("Library directory", d))
+        .chain(project.prebuilt_libs.iter().map(|l| ("Prebuilt library", l))) {
+        let abs_path = solution_root.join(&project.path).join(relative);
+        if !abs_path.exists() {
+            Console::log_fatal(format!("{} of project {} does not exist: {}", kind, project.name, abs_path.display()).as_str());
+            return Err("Failed to find a library of the project");
         }
-
-        // checks if there is still an error after creating the directories
-        let abs_dep_output_dir = solution_root
-            .join("output")
-            .join(&dep.path)
-            .canonicalize()
-            .map_err(|_| "Failed to create dependency output directory")?;
-
-        dep_output_dirs.push(abs_dep_output_dir);
     }
 
     Ok(ProjectBuildInputs {
         local_deps_in_order,
         include_dirs,
-        dep_output_dirs,
     })
 }
+
+/// Libraries, other than the local dependencies' artifacts, a project is linked with.
+#[derive(Default, Debug, PartialEq)]
+pub struct LinkLibraries {
+    /// Absolute directories searched for `system`.
+    pub library_dirs: Vec<PathBuf>,
+    /// Absolute paths of prebuilt libraries.
+    pub prebuilt: Vec<PathBuf>,
+    /// Library names (`pthread`), in link order.
+    pub system: Vec<String>,
+}
+
+///
+/// Finds the local dependencies `project` is linked with: its direct ones, and those reached through static libraries only.
+/// Shared libraries are linked with their own dependencies already, so they stop the propagation.
+/// # Arguments
+/// * `project` - The project being linked.
+/// * `dependencies` - The project's local dependencies, transitive and ordered so dependencies appear before dependents.
+/// # Returns
+/// * The dependencies to link, dependents before dependencies so static linkers resolve every symbol.
+///
+pub fn link_dependencies<'a>(project: &Project, dependencies: &'a [Project]) -> Vec<&'a Project> {
+    let mut reached: Vec<&Project> = Vec::new();
+    let mut pending: Vec<&Project> = vec![project];
+
+    while let Some(current) = pending.pop() {
+        for dep in &current.dependencies {
+            if let Some(dep_project) = dependencies.iter().find(|p| p.name == dep.name)
+                && !reached.iter().any(|p| p.name == dep_project.name) {
+                reached.push(dep_project);
+                if dep_project.project_type == ProjectType::StaticLib {
+                    pending.push(dep_project);
+                }
+            }
+        }
+    }
+
+    dependencies.iter().rev().filter(|d| reached.iter().any(|r| r.name == d.name)).collect()
+}
+
+///
+/// Collects the system libraries, library directories and prebuilt libraries `project` must be linked with:
+/// its own, and those of every static library it reaches through static libraries only.
+/// Shared libraries already carry theirs, so they stop the propagation.
+/// # Arguments
+/// * `project` - The project being linked.
+/// * `dependencies` - The project's local dependencies, transitive and ordered so dependencies appear before dependents.
+/// * `solution_root` - The root path of the solution.
+/// # Returns
+/// * The libraries, dependents' before dependencies' so static linkers resolve every symbol.
+///
+pub fn resolve_link_libraries(project: &Project, dependencies: &[Project], solution_root: &Path) -> LinkLibraries {
+    let static_libraries = link_dependencies(project, dependencies)
+        .into_iter()
+        .filter(|d| d.project_type == ProjectType::StaticLib);
+    let sources = std::iter::once(project).chain(static_libraries);
+
+    let mut libraries = LinkLibraries::default();
+    for source in sources {
+        let source_dir = solution_root.join(&source.path);
+
+        for dir in &source.library_dirs {
+            let dir = source_dir.join(dir);
+            if !libraries.library_dirs.contains(&dir) {
+                libraries.library_dirs.push(dir);
+            }
+        }
+
+        // A library needed twice must come after both of its users.
+        for lib in &source.prebuilt_libs {
+            let lib = source_dir.join(lib);
+            libraries.prebuilt.retain(|l| *l != lib);
+            libraries.prebuilt.push(lib);
+        }
+        for name in &source.system_libs {
+            libraries.system.retain(|l| l != name);
+            libraries.system.push(name.clone());
+        }
+    }
+
+    libraries
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::solution::{Project, ProjectType};
+    use crate::test_fixtures::{project, solution};
+    use super::{check_local_dependencies, find_cycles, link_dependencies, optional_dependency_define, resolve_link_libraries, select_projects};
+
+    fn project_with_libs(name: &str, project_type: ProjectType, deps: &[&str], system_libs: &[&str]) -> Project {
+        let mut project = project(name, project_type, deps);
+        project.system_libs = system_libs.iter().map(|l| l.to_string()).collect();
+        project
+    }
+
+    #[test]
+    fn system_libs_propagate_through_static_libraries_only() {
+        // app -> net (static) -> core (static), app -> gui (shared) -> x11 (static)
+        let core = project_with_libs("core", ProjectType::StaticLib, &[], &["m", "pthread"]);
+        let net = project_with_libs("net", ProjectType::StaticLib, &["core"], &["pthread", "ssl"]);
+        let x11 = project_with_libs("x11", ProjectType::StaticLib, &[], &["X11"]);
+        let gui = project_with_libs("gui", ProjectType::DynamicLib, &["x11"], &["dl"]);
+        let app = project_with_libs("app", ProjectType::Executable, &["net", "gui"], &["rt"]);
+
+        let dependencies = [core, net, x11, gui];
+        let libraries = resolve_link_libraries(&app, &dependencies, Path::new("/sol"));
+        assert_eq!(libraries.system, ["rt", "ssl", "m", "pthread"]);
+
+        let libraries = resolve_link_libraries(&dependencies[3], &dependencies[2..3], Path::new("/sol"));
+        assert_eq!(libraries.system, ["dl", "X11"]);
+    }
+
+    #[test]
+    fn libraries_behind_a_shared_library_are_not_linked_again() {
+        // app -> net (static) -> core (static), app -> gui (shared) -> x11 (static), gui -> font (shared), net -> tls (shared)
+        let core = project("core", ProjectType::StaticLib, &[]);
+        let tls = project("tls", ProjectType::DynamicLib, &[]);
+        let net = project("net", ProjectType::StaticLib, &["core", "tls"]);
+        let x11 = project("x11", ProjectType::StaticLib, &[]);
+        let font = project("font", ProjectType::DynamicLib, &[]);
+        let gui = project("gui", ProjectType::DynamicLib, &["x11", "font"]);
+        let app = project("app", ProjectType::Executable, &["net", "gui"]);
+
+        let dependencies = [core, tls, net, x11, font, gui];
+        let names = |projects: Vec<&Project>| projects.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(link_dependencies(&app, &dependencies)), ["gui", "net", "tls", "core"]);
+        assert_eq!(names(link_dependencies(&dependencies[5], &dependencies[3..5])), ["font", "x11"]);
+    }
+
+    #[test]
+    fn optional_dependencies_are_defined_or_skipped() {
+        let mut app = project("app", ProjectType::Executable, &["net", "gui"]);
+        app.dependencies[1].version = "^2.0.0".to_string();
+        for dep in &mut app.dependencies {
+            dep.optional = true;
+        }
+        let net = project("net", ProjectType::StaticLib, &[]);
+        let gui = project("gui", ProjectType::StaticLib, &[]);
+
+        let mut sol = solution(vec![app, net, gui]);
+        assert!(check_local_dependencies(&mut sol, false).is_ok());
+        assert_eq!(sol.projects[0].defines, ["HAS_NET"]);
+        assert_eq!(optional_dependency_define("gtk+-3.0"), "HAS_GTK__3_0");
+    }
+
+    #[test]
+    fn required_dependency_without_matching_version_fails() {
+        let mut app = project("app", ProjectType::Executable, &["net"]);
+        app.dependencies[0].version = ">=2.0.0".to_string();
+        let mut sol = solution(vec![app, project("net", ProjectType::StaticLib, &[])]);
+        assert!(check_local_dependencies(&mut sol, false).is_err());
+    }
+
+    #[test]
+    fn cycles_are_reported_as_chains() {
+        // 0 -> 1 -> 2 -> 0, 2 -> 3 -> 3, 4 -> 1
+        let edges = vec![vec![1], vec![2], vec![0, 3], vec![3], vec![1]];
+        assert_eq!(find_cycles(&edges), [vec![0, 1, 2, 0], vec![3, 3]]);
+        assert!(find_cycles(&[vec![1], vec![2], vec![]]).is_empty());
+
+        // 0 -> 1 -> 2 -> 0, 0 -> 2: the shorter cycle shares its nodes with the longer one
+        let edges = vec![vec![1, 2], vec![2], vec![0]];
+        assert_eq!(find_cycles(&edges), [vec![0, 1, 2, 0], vec![0, 2, 0]]);
+
+        // Two cycles through 1 that a back edge search reports once: 1 -> 2 -> 1 and 1 -> 3 -> 2 -> 1
+        let edges = vec![vec![1], vec![2, 3], vec![1], vec![2]];
+        assert_eq!(find_cycles(&edges), [vec![1, 2, 1], vec![1, 3, 2, 1]]);
+
+        let mut app = project("app", ProjectType::Executable, &["net"]);
+        app.dependencies[0].optional = true;
+        let net = project("net", ProjectType::StaticLib, &["util"]);
+        let util = project("util", ProjectType::StaticLib, &["app"]);
+        let mut sol = solution(vec![app, net, util]);
+        assert!(check_local_dependencies(&mut sol, false).is_err());
+
+        // Rejected by version, so not an edge
+        sol.projects[0].dependencies[0].version = "2.0.0".to_string();
+        assert!(check_local_dependencies(&mut sol, false).is_ok());
+    }
+
+    #[test]
+    fn selected_projects_come_with_their_dependencies() {
+        let sol = solution(vec![
+            project("app", ProjectType::Executable, &["net"]),
+            project("tool", ProjectType::Executable, &["util"]),
+            project("net", ProjectType::StaticLib, &["util"]),
+            project("util", ProjectType::StaticLib, &[]),
+        ]);
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<String>>();
+
+        assert_eq!(select_projects(&sol, &names(&["app"]), &[]).unwrap(), ["util", "net", "app"]);
+        assert_eq!(select_projects(&sol, &[], &names(&["app"])).unwrap(), ["util", "tool", "net"]);
+        assert!(select_projects(&sol, &names(&["app"]), &names(&["util"])).is_err());
+        assert!(select_projects(&sol, &names(&["nope"]), &[]).is_err());
+    }
+}
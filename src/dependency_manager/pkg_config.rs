@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::dependency_manager::local_resolve::{optional_dependency_define, report_unresolved_dependency};
+use crate::helpers::console::Console;
+use crate::helpers::version_tools::{external_version_check, Version};
+use crate::solution::{Project, Solution};
+
+/// A module found by pkg-config, with the flags needed to build against it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PkgConfigPackage {
+    pub name: String,
+    pub version: String,
+    /// Output of `pkg-config --cflags`, split into arguments.
+    pub cflags: Vec<String>,
+    /// Output of `pkg-config --libs`, split into arguments.
+    pub libs: Vec<String>,
+}
+
+/// The pkg-config program: `$PKG_CONFIG` if set, `pkg-config` otherwise.
+pub fn pkg_config_program() -> String {
+    env::var("PKG_CONFIG").ok().filter(|p| !p.trim().is_empty()).unwrap_or("pkg-config".to_string())
+}
+
+///
+/// Looks a module up with pkg-config.
+/// # Arguments
+/// * `name` - The module name (e.g. `zlib`).
+/// # Returns
+/// * The module's version and flags, or why it could not be found.
+///
+pub fn query_package(name: &str) -> Result<PkgConfigPackage, String> {
+    let run = |option: &str| -> Result<String, String> {
+        let output = Command::new(pkg_config_program())
+            .arg(option)
+            .arg(name)
+            .output()
+            .map_err(|e| format!("failed to run {}: {}", pkg_config_program(), e))?;
+
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = stderr.lines().next().unwrap_or("").trim().to_string();
+            Err(if message.is_empty() { format!("pkg-config does not know {}", name) } else { message })
+        }
+    };
+
+    Ok(PkgConfigPackage {
+        name: name.to_string(),
+        version: run("--modversion")?,
+        cflags: split_flags(&run("--cflags")?),
+        libs: split_flags(&run("--libs")?),
+    })
+}
+
+///
+/// Splits pkg-config output into arguments. pkg-config escapes spaces and quotes with backslashes;
+/// quoted strings from hand-written `.pc` files are honoured too.
+///
+pub fn split_flags(flags: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    let mut chars = flags.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', q) if q != Some('\'') => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+                in_arg = true;
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (c, Some(_)) => current.push(c),
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (c, None) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (c, None) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if in_arg {
+        args.push(current);
+    }
+
+    args
+}
+
+///
+/// Adds the flags of `package` to `project`, translated to the project's own settings so every backend understands them:
+/// `-I` to `additional_includes`, `-D` to `defines`, `-L` to `library_dirs`, `-l` to `system_libs`
+/// and everything else to `compile_flags` or `link_flags`.
+///
+pub fn apply_package(project: &mut Project, package: &PkgConfigPackage) {
+    let mut cflags = package.cflags.iter();
+    while let Some(flag) = cflags.next() {
+        if let Some(dir) = flag_value(flag, "-I", &mut cflags) {
+            project.additional_includes.push(PathBuf::from(dir));
+        } else if let Some(define) = flag_value(flag, "-D", &mut cflags) {
+            project.defines.push(define);
+        } else {
+            project.compile_flags.push(flag.clone());
+        }
+    }
+
+    let mut libs = package.libs.iter();
+    while let Some(flag) = libs.next() {
+        if let Some(dir) = flag_value(flag, "-L", &mut libs) {
+            project.library_dirs.push(PathBuf::from(dir));
+        } else if let Some(lib) = flag_value(flag, "-l", &mut libs) {
+            project.system_libs.push(lib);
+        } else {
+            project.link_flags.push(flag.clone());
+        }
+    }
+}
+
+// Value of `-Ifoo` or `-I foo`.
+fn flag_value<'a>(flag: &str, prefix: &str, rest: &mut impl Iterator<Item = &'a String>) -> Option<String> {
+    let value = flag.strip_prefix(prefix)?;
+    if value.is_empty() {
+        rest.next().cloned()
+    } else {
+        Some(value.to_string())
+    }
+}
+
+///
+/// Resolves, through pkg-config, the dependencies that are not projects of the solution,
+/// and adds their flags to the projects depending on them (and `HAS_<NAME>` to their defines if the dependency is optional).
+/// # Arguments
+/// * `solution` - The solution; its projects are updated in place.
+/// * `verbose` - Whether to enable verbose output.
+/// # Returns
+/// * An error if a required dependency is not found or has no matching version, once every project has been checked.
+///
+pub fn resolve_external_dependencies(solution: &mut Solution, verbose: bool) -> Result<(), &'static str> {
+    let local_names: Vec<String> = solution.projects.iter().map(|p| p.name.clone()).collect();
+    let mut cache: HashMap<String, Result<PkgConfigPackage, String>> = HashMap::new();
+    let mut result = Ok(());
+
+    for project in &mut solution.projects {
+        let external: Vec<_> = project.dependencies.iter().filter(|d| !local_names.contains(&d.name)).cloned().collect();
+
+        for dep in external {
+            let package = cache.entry(dep.name.clone()).or_insert_with(|| {
+                Console::log_verbose(format!("Looking up {} with pkg-config", dep.name).as_str(), verbose);
+                query_package(&dep.name)
+            });
+
+            let unresolved = match package {
+                Ok(package) if external_version_check(&dep.version, &package.version) => {
+                    Console::log_info(format!(
+                        "Found external dependency: {} version {} (pkg-config) for project {}",
+                        package.name, package.version, project.name
+                    ).as_str());
+                    apply_package(project, package);
+                    if dep.optional {
+                        project.defines.push(optional_dependency_define(&dep.name));
+                    }
+                    continue;
+                }
+                Ok(package) if Version::parse_lenient(&package.version).is_err() => report_unresolved_dependency(
+                    project,
+                    &dep,
+                    format!("pkg-config version `{}` is not a version (major[.minor[.patch]])", package.version).as_str(),
+                    &[],
+                ),
+                Ok(package) => report_unresolved_dependency(
+                    project,
+                    &dep,
+                    "pkg-config has no matching version",
+                    &[format!("{} {} (pkg-config)", package.name, package.version)],
+                ),
+                Err(e) => report_unresolved_dependency(
+                    project,
+                    &dep,
+                    format!("not a project of the solution, and not found by pkg-config ({})", e).as_str(),
+                    &[],
+                ),
+            };
+
+            if unresolved.is_err() {
+                result = unresolved;
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::solution::ProjectType;
+    use crate::test_fixtures::project;
+    use super::{apply_package, split_flags, PkgConfigPackage};
+
+    #[test]
+    fn splits_escaped_and_quoted_flags() {
+        assert_eq!(split_flags("-I/usr/include/foo -DFOO=1  -pthread"), ["-I/usr/include/foo", "-DFOO=1", "-pthread"]);
+        assert_eq!(split_flags("-I/opt/my\\ lib/include -DNAME=\"a b\""), ["-I/opt/my lib/include", "-DNAME=a b"]);
+        assert!(split_flags("  ").is_empty());
+    }
+
+    #[test]
+    fn flags_are_translated_to_project_settings() {
+        let mut project = project("app", ProjectType::Executable, &[]);
+        let package = PkgConfigPackage {
+            name: "foo".to_string(),
+            version: "1.0.0".to_string(),
+            cflags: split_flags("-I/opt/foo/include -DFOO -pthread"),
+            libs: split_flags("-L /opt/foo/lib -lfoo -pthread"),
+        };
+
+        apply_package(&mut project, &package);
+
+        assert_eq!(project.additional_includes, [PathBuf::from("/opt/foo/include")]);
+        assert_eq!(project.defines, ["FOO"]);
+        assert_eq!(project.compile_flags, ["-pthread"]);
+        assert_eq!(project.library_dirs, [PathBuf::from("/opt/foo/lib")]);
+        assert_eq!(project.system_libs, ["foo"]);
+        assert_eq!(project.link_flags, ["-pthread"]);
+    }
+}
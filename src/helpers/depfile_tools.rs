@@ -0,0 +1,151 @@
+use std::fs::{read_to_string, write};
+use std::io;
+use std::path::{Path, PathBuf};
+
+///
+/// Reads a Makefile-syntax dependency file, as emitted by `gcc -MMD -MF <file>`.
+/// # Arguments
+/// * `depfile_path` - Path to the dependency file.
+/// # Returns
+/// * Every prerequisite listed in the file, or `None` if it cannot be read.
+///
+pub fn read_depfile(depfile_path: &Path) -> Option<Vec<PathBuf>> {
+    read_to_string(depfile_path).ok().map(|contents| parse_depfile(&contents))
+}
+
+///
+/// Parses the contents of a Makefile-syntax dependency file (`target: prereq prereq \`).
+/// Handles line continuations, escaped spaces (`\ `), escaped hashes (`\#`) and doubled dollars (`$$`).
+/// Backslashes that do not escape anything are kept so Windows paths survive.
+/// # Arguments
+/// * `contents` - Contents of the dependency file.
+/// # Returns
+/// * Every prerequisite, in order of appearance and without duplicates. Targets are not included.
+///
+pub fn parse_depfile(contents: &str) -> Vec<PathBuf> {
+    let mut prerequisites: Vec<PathBuf> = Vec::new();
+
+    // Line continuations turn the file into one logical rule per line.
+    let joined = contents.replace("\\\r\n", " ").replace("\\\n", " ");
+
+    for line in joined.lines() {
+        let Some(rule) = line.find(": ").or_else(|| line.strip_suffix(':').map(|l| l.len())) else {
+            continue;
+        };
+
+        for token in tokenize(&line[rule + 1..]) {
+            let path = PathBuf::from(token);
+            if !prerequisites.contains(&path) {
+                prerequisites.push(path);
+            }
+        }
+    }
+
+    prerequisites
+}
+
+///
+/// Writes a Makefile-syntax dependency file, for compilers that report their includes another way (MSVC's `/showIncludes`).
+/// # Arguments
+/// * `depfile_path` - Path to the dependency file.
+/// * `target` - The file the rule produces (the object file).
+/// * `prerequisites` - The files the target was built from.
+///
+pub fn write_depfile(depfile_path: &Path, target: &Path, prerequisites: &[PathBuf]) -> io::Result<()> {
+    write(depfile_path, format_depfile(target, prerequisites))
+}
+
+/// Formats a single rule the way `parse_depfile` reads it back, one prerequisite per line.
+pub fn format_depfile(target: &Path, prerequisites: &[PathBuf]) -> String {
+    let mut contents = format!("{}:", escape(target));
+    for prerequisite in prerequisites {
+        contents.push_str(" \\\n ");
+        contents.push_str(&escape(prerequisite));
+    }
+    contents.push('\n');
+    contents
+}
+
+fn escape(path: &Path) -> String {
+    path.to_string_lossy().replace('$', "$$").replace(' ', "\\ ").replace('#', "\\#")
+}
+
+// Splits the prerequisites part of a rule on unescaped whitespace.
+fn tokenize(prerequisites: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = prerequisites.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if matches!(chars.peek(), Some(' ') | Some('#')) => {
+                current.push(chars.next().unwrap());
+            }
+            '$' if chars.peek() == Some(&'$') => {
+                chars.next();
+                current.push('$');
+            }
+            c if c.is_whitespace() => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::path::Path;
+    use super::{format_depfile, parse_depfile};
+
+    fn paths(list: &[&str]) -> Vec<PathBuf> {
+        list.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn single_line_rule() {
+        let deps = parse_depfile("main.cpp.o: /src/main.cpp /src/lib.h\n");
+        assert_eq!(deps, paths(&["/src/main.cpp", "/src/lib.h"]));
+    }
+
+    #[test]
+    fn line_continuations() {
+        let deps = parse_depfile("main.cpp.o: /src/main.cpp \\\n /beta/lib.h \\\n /beta/other.h\n");
+        assert_eq!(deps, paths(&["/src/main.cpp", "/beta/lib.h", "/beta/other.h"]));
+    }
+
+    #[test]
+    fn escaped_characters() {
+        let deps = parse_depfile("a.o: /my\\ dir/a.c /cost$$/b\\#1.h\n");
+        assert_eq!(deps, paths(&["/my dir/a.c", "/cost$/b#1.h"]));
+    }
+
+    #[test]
+    fn phony_targets_and_duplicates() {
+        // -MP style output adds empty rules for every header
+        let deps = parse_depfile("a.o: a.c b.h\nb.h:\n");
+        assert_eq!(deps, paths(&["a.c", "b.h"]));
+    }
+
+    #[test]
+    fn windows_drive_letters() {
+        let deps = parse_depfile("C:\\out\\a.o: C:\\src\\a.c C:\\src\\b.h\n");
+        assert_eq!(deps, paths(&["C:\\src\\a.c", "C:\\src\\b.h"]));
+    }
+
+    #[test]
+    fn formatted_depfile_round_trips() {
+        let headers = paths(&["C:\\src\\a.c", "C:\\My Headers\\b#1.h", "/cost$/c.h"]);
+        let contents = format_depfile(Path::new("C:\\out\\a.c.obj"), &headers);
+        assert_eq!(parse_depfile(&contents), headers);
+    }
+}
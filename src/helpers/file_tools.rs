@@ -30,24 +30,76 @@ pub fn list_files(root: &PathBuf) -> io::Result<Vec<PathBuf>> {
 }
 
 ///
-/// Returns all the object files (.o) found in the given list of files.
-/// DOES NOT search for files in the filesystem; use `list_files` for that.
-/// # Arguments
-/// * `files` - A vector of PathBuf representing files to search through.
-/// * `path` - The base path to join with found object files.
-/// # Returns
-/// * A vector of PathBuf containing the full paths to the found object files.
-///
-pub fn find_object_files(files: &Vec<PathBuf>, path: &PathBuf) -> Vec<PathBuf> {
-    let mut found_object_files: Vec<PathBuf> = Vec::new();
-
-    for file in files {
-        let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("");
-        if ext.eq_ignore_ascii_case("o") {
-            let obj_path = path.join(&file);
-            found_object_files.push(obj_path);
+/// Computes the path leading from the `from` directory to `to`, e.g. `/out/alpha` -> `/out/beta` gives `../beta`.
+/// Both paths are expected to be absolute.
+///
+pub fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<_> = from.components().filter(|c| *c != std::path::Component::CurDir).collect();
+    let to: Vec<_> = to.components().filter(|c| *c != std::path::Component::CurDir).collect();
+
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component);
+    }
+    relative
+}
+
+///
+/// Creates (or replaces) a symbolic link at `link` pointing to `target`.
+/// On platforms without symlinks, the target is copied instead.
+///
+pub fn replace_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    if link.symlink_metadata().is_ok() {
+        fs::remove_file(link)?;
+    }
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, link)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let source = link.parent().map(|p| p.join(target)).unwrap_or(target.to_path_buf());
+        fs::copy(source, link).map(|_| ())
+    }
+}
+
+///
+/// Returns `path` canonicalized if it exists. Otherwise its `.` and `..` components are resolved without touching
+/// the filesystem, so a directory that is not created yet gets the path it will canonicalize to.
+/// `path` is expected to be absolute.
+///
+pub fn absolute_path(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
         }
     }
+    normalized
+}
 
-    found_object_files
-}
\ No newline at end of file
+///
+/// Checks whether `path` is inside `dir` once `.`, `..` and the symbolic links of its parent directories are resolved.
+/// `path` itself may be a symbolic link pointing elsewhere. `dir` is expected to be canonical.
+///
+pub fn is_within(path: &Path, dir: &Path) -> bool {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => absolute_path(parent).join(name).starts_with(dir),
+        _ => false,
+    }
+}
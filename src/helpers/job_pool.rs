@@ -0,0 +1,166 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+///
+/// Limits how many jobs (compiler or linker processes) run at the same time.
+/// A single pool is shared by everything that spawns processes, so the limit is global to the build.
+///
+pub struct JobPool {
+    jobs: usize,
+    keep_going: bool,
+    running: Mutex<usize>,
+    slot_freed: Condvar,
+    failed: AtomicBool,
+}
+
+/// A running job. The slot is given back to the pool when dropped.
+pub struct JobSlot<'a> {
+    pool: &'a JobPool,
+}
+
+impl Drop for JobSlot<'_> {
+    fn drop(&mut self) {
+        let mut running = self.pool.running.lock().unwrap();
+        *running -= 1;
+        self.pool.slot_freed.notify_one();
+    }
+}
+
+impl JobPool {
+    ///
+    /// Creates a pool running at most `jobs` jobs at once (at least one).
+    /// # Arguments
+    /// * `jobs` - Maximum number of concurrent jobs.
+    /// * `keep_going` - Whether to keep scheduling new jobs after one failed.
+    ///
+    pub fn new(jobs: usize, keep_going: bool) -> Self {
+        JobPool {
+            jobs: jobs.max(1),
+            keep_going,
+            running: Mutex::new(0),
+            slot_freed: Condvar::new(),
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    /// Number of jobs the machine can run in parallel. Used as the default for `--jobs`.
+    pub fn default_jobs() -> usize {
+        thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+    }
+
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    /// Blocks until a job slot is available.
+    pub fn acquire(&self) -> JobSlot<'_> {
+        let mut running = self.running.lock().unwrap();
+        while *running >= self.jobs {
+            running = self.slot_freed.wait(running).unwrap();
+        }
+        *running += 1;
+        JobSlot { pool: self }
+    }
+
+    /// Records that a job failed.
+    pub fn mark_failed(&self) {
+        self.failed.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether a job failed and no new work should be scheduled because of it.
+    pub fn should_stop(&self) -> bool {
+        !self.keep_going && self.failed.load(Ordering::SeqCst)
+    }
+
+    ///
+    /// Runs `job` on every task, at most `jobs` at a time.
+    /// Once a job fails, tasks that did not start yet are skipped unless the pool keeps going.
+    /// # Arguments
+    /// * `tasks` - The tasks to run.
+    /// * `job` - The function run for each task.
+    /// # Returns
+    /// * One entry per task, in the same order. `None` means the task was skipped.
+    ///
+    pub fn run<T, R, E, F>(&self, tasks: &[T], job: F) -> Vec<Option<Result<R, E>>>
+    where
+        T: Sync,
+        R: Send,
+        E: Send,
+        F: Fn(&T) -> Result<R, E> + Sync,
+    {
+        let next_task = Mutex::new(0usize);
+        let results: Vec<Mutex<Option<Result<R, E>>>> = tasks.iter().map(|_| Mutex::new(None)).collect();
+        let workers = self.jobs.min(tasks.len());
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let index = {
+                        let mut next = next_task.lock().unwrap();
+                        if *next >= tasks.len() {
+                            break;
+                        }
+                        *next += 1;
+                        *next - 1
+                    };
+
+                    let _slot = self.acquire();
+                    if self.should_stop() {
+                        continue;
+                    }
+
+                    let result = job(&tasks[index]);
+                    if result.is_err() {
+                        self.mark_failed();
+                    }
+                    *results[index].lock().unwrap() = Some(result);
+                });
+            }
+        });
+
+        results.into_iter().map(|r| r.into_inner().unwrap()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread::sleep;
+    use std::time::Duration;
+    use super::JobPool;
+
+    #[test]
+    fn never_exceeds_job_count() {
+        let pool = JobPool::new(2, false);
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        let results = pool.run(&[0; 8], |_| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            sleep(Duration::from_millis(10));
+            running.fetch_sub(1, Ordering::SeqCst);
+            Ok::<(), ()>(())
+        });
+
+        assert!(results.iter().all(|r| matches!(r, Some(Ok(())))));
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn stops_scheduling_after_failure() {
+        let pool = JobPool::new(1, false);
+        let results = pool.run(&[1, 2, 3], |n| if *n == 1 { Err(()) } else { Ok(()) });
+        assert!(matches!(results[0], Some(Err(()))));
+        assert!(results[1].is_none() && results[2].is_none());
+    }
+
+    #[test]
+    fn keeps_going_after_failure() {
+        let pool = JobPool::new(1, true);
+        let results = pool.run(&[1, 2, 3], |n| if *n == 1 { Err(()) } else { Ok(()) });
+        assert!(matches!(results[0], Some(Err(()))));
+        assert!(matches!(results[2], Some(Ok(()))));
+    }
+}
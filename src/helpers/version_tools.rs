@@ -1,148 +1,411 @@
-//noinspection D
-/// Compares two version strings for equality. This function compares two versions from the format "<mod>major.minor.patch".
-/// Here are the available modifiers:
-/// - "^": Compatible with version (e.g., "^1.2.3" is compatible with "1.3.0" but not "2.0.0").
-/// - "~": Approximately equivalent to version (e.g., "~1.2.3" is approximately equivalent to "1.2.5" but not "1.3.0")
-/// - ">" : Greater than version.
-/// - "<" : Less than version.
-/// - ">=": Greater than or equal to version.
-/// - "<=": Less than or equal to version.
-/// If no modifier is present, exact match is required.
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
 ///
-/// # Arguments
-/// * `required_version` - The version string that needs to be satisfied. contains the modifier.
-/// * `provided_version` - The version string to check against the required version.
-/// # Returns
-/// * `true` if the versions are equal, `false` otherwise.
-pub fn version_check(required_version: &str, provided_version: &str) -> bool {
+/// A semantic version (SemVer 2.0): `major.minor.patch`, optionally followed by a pre-release (`-beta.2`)
+/// and build metadata (`+build.5`).
+/// Versions are ordered by precedence: build metadata is ignored, and a pre-release comes before its release.
+///
+#[derive(Clone, Debug)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub pre: Vec<Prerelease>,
+    pub build: Vec<String>,
+}
 
-    // Safely get the first character, if any
-    let mut chars_iter = required_version.chars();
-    let first_char_opt = chars_iter.next();
-    if first_char_opt.is_none() {
-        // Empty required_version cannot satisfy any constraint
-        return false;
-    }
-    let first_char = first_char_opt.unwrap();
-    let first_char_parse = first_char.to_string().parse::<i32>();
-    let mut modifier_a: String = "==".to_string(); // Default to exact match
-
-    // If changing the first character to an integer fails, it means it's not a digit
-    if first_char_parse.is_err() {
-        // Has modifier
-        modifier_a = first_char.to_string();
-
-        if modifier_a.eq(">") || modifier_a.eq("<") {
-            // Check for two character modifiers (e.g., ">=" or "<=") safely
-            if let Some(second_char) = chars_iter.next() {
-                if second_char.eq(&'=') {
-                    modifier_a.push(second_char);
-                }
-            }
+/// One dot-separated identifier of a pre-release. Numeric identifiers come before alphanumeric ones.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Prerelease {
+    Numeric(u64),
+    Alphanumeric(String),
+}
+
+impl Version {
+    pub fn new(major: u64, minor: u64, patch: u64) -> Version {
+        Version { major, minor, patch, pre: Vec::new(), build: Vec::new() }
+    }
+
+    ///
+    /// Parses a version reported by an external tool, such as pkg-config, which does not always follow SemVer:
+    /// missing minor and patch numbers are 0 (`3.11` is `3.11.0`), numbers may have leading zeros, and numbers
+    /// after the patch are kept as build metadata (`4.8.12.0` is `4.8.12+0`), so they do not change its precedence.
+    /// Versions written in the solution are parsed strictly, with `parse`.
+    ///
+    pub fn parse_lenient(s: &str) -> Result<Version, String> {
+        let (core, pre, build) = split_version(s.trim())?;
+
+        let numbers = core
+            .split('.')
+            .map(|part| match part.parse::<u64>() {
+                Ok(n) if part.chars().all(|c| c.is_ascii_digit()) => Ok(n),
+                _ => Err(format!("`{}' is not a version: `{}' is not a number", s, part)),
+            })
+            .collect::<Result<Vec<u64>, String>>()?;
+        let number = |i: usize| numbers.get(i).copied().unwrap_or(0);
+
+        let mut version = Version::new(number(0), number(1), number(2));
+        version.pre = pre;
+        version.build = numbers.iter().skip(3).map(u64::to_string).chain(build).collect();
+        Ok(version)
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        !self.pre.is_empty()
+    }
+
+    fn triple(&self) -> (u64, u64, u64) {
+        (self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Version, String> {
+        let (core, pre, build) = split_version(s.trim())?;
+
+        let parts: Vec<&str> = core.split('.').collect();
+        if parts.len() != 3 {
+            return Err(format!("`{}' is not a version: expected major.minor.patch", s));
         }
+
+        let mut version = Version::new(parse_number(parts[0], s)?, parse_number(parts[1], s)?, parse_number(parts[2], s)?);
+        version.pre = pre;
+        version.build = build;
+        Ok(version)
     }
+}
 
-    // Cleans version strings by removing modifiers
-    let clean_required_version = required_version.replace(&['<', '>', '=', '^', '~'][..], "");
-    let clean_provided_version = provided_version.replace(&['<', '>', '=', '^', '~'][..], "");
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            let pre: Vec<String> = self.pre.iter().map(|p| match p {
+                Prerelease::Numeric(n) => n.to_string(),
+                Prerelease::Alphanumeric(s) => s.clone(),
+            }).collect();
+            write!(f, "-{}", pre.join("."))?;
+        }
+        if !self.build.is_empty() {
+            write!(f, "+{}", self.build.join("."))?;
+        }
+        Ok(())
+    }
+}
 
+impl Ord for Version {
+    fn cmp(&self, other: &Version) -> Ordering {
+        self.triple().cmp(&other.triple()).then_with(|| {
+            match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => self.pre.cmp(&other.pre),
+            }
+        })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Equality is precedence too, so `1.0.0+a == 1.0.0+b`.
+impl PartialEq for Version {
+    fn eq(&self, other: &Version) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
 
-    // MY EEYYYYYYYYESSSSSS
-    if modifier_a.eq("^") {                         // Compatible with version (same major)
-        major_eq(clean_provided_version, clean_required_version)
+impl Eq for Version {}
 
-    } else if modifier_a.eq("~") {                  // Approximately equivalent to version (same major and minor)
-        major_eq(clean_provided_version.clone(), clean_required_version.clone())
-            && minor_eq(clean_provided_version, clean_required_version)
+/// Operator of a `Comparator`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    /// No operator, or `=`.
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    /// `~`: same major and minor (same major if only the major is given).
+    Tilde,
+    /// `^`: no change to the leftmost non-zero part.
+    Caret,
+    /// `1.2.*`: any version starting with the given parts.
+    Wildcard,
+}
 
-    } else if modifier_a.eq(">") {                  // Greater than version
-        // provided_version must be strictly greater than required_version
-        greater_than(clean_provided_version, clean_required_version)
+///
+/// A single condition of a `VersionReq`, such as `^1.2.3` or `>=2.0`:
+/// - `1.2.3` or `=1.2.3`: exactly that version.
+/// - `>`, `>=`, `<`, `<=`: compared by precedence. Missing minor and patch count as 0 (`>1` is `>1.0.0`).
+/// - `~1.2.3`: at least 1.2.3, below 1.3.0. `~1` is below 2.0.0.
+/// - `^1.2.3`: at least 1.2.3, below 2.0.0. Zero parts are not compatible with each other:
+///   `^0.2.1` is below 0.3.0 and `^0.0.3` below 0.0.4.
+/// - `1.2.*` and `1.*` (or `x`, `X`): any version starting with `1.2.` or `1.`.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparator {
+    pub op: Op,
+    pub major: u64,
+    pub minor: Option<u64>,
+    pub patch: Option<u64>,
+    pub pre: Vec<Prerelease>,
+}
 
-    } else if modifier_a.eq("<") {                  // Less than version
-        // provided_version must be strictly less than required_version
-        greater_than(clean_required_version, clean_provided_version)
+///
+/// A version requirement: comparators joined by `,` (all must hold) and alternatives separated by `||`
+/// (one must hold), such as `>=1.2, <2.0` or `^1.0 || ^2.0`. `*` alone accepts any version.
+///
+/// A pre-release version only satisfies an alternative with a comparator on a pre-release of the same major.minor.patch
+/// (`>=1.2.3-alpha` accepts `1.2.3-beta`, not `1.3.0-beta`), so stable requirements never pick unstable versions.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionReq {
+    pub alternatives: Vec<Vec<Comparator>>,
+}
 
-    } else if modifier_a.eq(">=") {                 // Greater than or equal to version
-        greater_than(clean_provided_version.clone(), clean_required_version.clone())
-            || clean_provided_version.eq(&clean_required_version)
+impl FromStr for VersionReq {
+    type Err = String;
 
-    } else if modifier_a.eq("<=") {                 // Less than or equal to version
-        greater_than(clean_required_version.clone(), clean_provided_version.clone())
-            || clean_provided_version.eq(&clean_required_version)
+    fn from_str(s: &str) -> Result<VersionReq, String> {
+        if s.trim().is_empty() {
+            return Err("empty version requirement".to_string());
+        }
 
-    } else {                                              // Exact match required (same major, minor, and patch)
-        required_version.eq(provided_version)
+        let alternatives = s
+            .split("||")
+            .map(|alternative| {
+                alternative
+                    .split(',')
+                    .map(str::trim)
+                    .map(|comparator| match comparator {
+                        "" => Err(format!("`{}' is not a version requirement: empty condition", s)),
+                        "*" | "x" | "X" => Ok(None),
+                        _ => comparator.parse::<Comparator>().map(Some),
+                    })
+                    .filter_map(Result::transpose)
+                    .collect::<Result<Vec<Comparator>, String>>()
+            })
+            .collect::<Result<Vec<_>, String>>()?;
 
+        Ok(VersionReq { alternatives })
     }
+}
+
+impl VersionReq {
+    /// Whether `version` satisfies the requirement.
+    pub fn matches(&self, version: &Version) -> bool {
+        self.alternatives.iter().any(|comparators| {
+            let prerelease_allowed = !version.is_prerelease()
+                || comparators.iter().any(|c| !c.pre.is_empty() && c.lower_bound().triple() == version.triple());
 
+            prerelease_allowed && comparators.iter().all(|c| c.matches(version))
+        })
+    }
 }
 
-// Checks for major version equality
-fn major_eq(version_a: String, version_b: String) -> bool {
-    let major_a = version_a.split('.').next().unwrap_or("");
-    let major_b = version_b.split('.').next().unwrap_or("");
-    major_a == major_b
+impl FromStr for Comparator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Comparator, String> {
+        let trimmed = s.trim();
+        let (op, rest) = [
+            (">=", Op::GreaterEq),
+            ("<=", Op::LessEq),
+            (">", Op::Greater),
+            ("<", Op::Less),
+            ("=", Op::Exact),
+            ("~", Op::Tilde),
+            ("^", Op::Caret),
+        ]
+        .iter()
+        .find_map(|(prefix, op)| trimmed.strip_prefix(prefix).map(|rest| (*op, rest)))
+        .unwrap_or((Op::Exact, trimmed));
+
+        let (core, pre, _build) = split_version(rest.trim_start())?;
+        let mut parts: Vec<&str> = core.split('.').collect();
+        if parts.len() > 3 {
+            return Err(format!("`{}' is not a version requirement: too many parts", s));
+        }
+
+        // `1.2.*`: the wildcard ends the version and replaces the `=`.
+        let mut op = op;
+        if let Some(wildcard) = parts.iter().position(|p| matches!(*p, "*" | "x" | "X")) {
+            if wildcard != parts.len() - 1 || wildcard == 0 || op != Op::Exact || !pre.is_empty() {
+                return Err(format!("`{}' is not a version requirement: a wildcard can only end a version, as in `1.2.*`", s));
+            }
+            parts.pop();
+            op = Op::Wildcard;
+        }
+
+        if !pre.is_empty() && parts.len() != 3 {
+            return Err(format!("`{}' is not a version requirement: a pre-release needs major.minor.patch", s));
+        }
+
+        Ok(Comparator {
+            op,
+            major: parse_number(parts[0], s)?,
+            minor: parts.get(1).map(|p| parse_number(p, s)).transpose()?,
+            patch: parts.get(2).map(|p| parse_number(p, s)).transpose()?,
+            pre,
+        })
+    }
 }
 
-// Checks for minor version equality
-fn minor_eq(version_a: String, version_b: String) -> bool {
-    let parts_a: Vec<&str> = version_a.split('.').collect();
-    let parts_b: Vec<&str> = version_b.split('.').collect();
+impl Comparator {
+    /// Whether `version` satisfies the comparator, pre-releases aside (see `VersionReq`).
+    pub fn matches(&self, version: &Version) -> bool {
+        let lower = self.lower_bound();
+        match self.op {
+            Op::Exact => *version == lower,
+            Op::Greater => *version > lower,
+            Op::GreaterEq => *version >= lower,
+            Op::Less => *version < lower,
+            Op::LessEq => *version <= lower,
+            Op::Tilde | Op::Caret | Op::Wildcard => *version >= lower && *version < self.upper_bound(),
+        }
+    }
 
-    if parts_a.len() < 2 || parts_b.len() < 2 {
-        return false;
+    // The version written in the comparator, missing parts being 0.
+    fn lower_bound(&self) -> Version {
+        Version {
+            pre: self.pre.clone(),
+            ..Version::new(self.major, self.minor.unwrap_or(0), self.patch.unwrap_or(0))
+        }
     }
 
-    parts_a[1] == parts_b[1]
+    // First version excluded by `~`, `^` and wildcards.
+    fn upper_bound(&self) -> Version {
+        match (self.op, self.minor, self.patch) {
+            (Op::Tilde | Op::Wildcard, Some(minor), _) => Version::new(self.major, minor + 1, 0),
+            (Op::Caret, _, _) if self.major > 0 => Version::new(self.major + 1, 0, 0),
+            (Op::Caret, Some(0), Some(patch)) => Version::new(0, 0, patch + 1),
+            (Op::Caret, Some(minor), _) => Version::new(0, minor + 1, 0),
+            _ => Version::new(self.major + 1, 0, 0),
+        }
+    }
 }
 
-// Checks if version_a is greater than version_b
-fn greater_than(version_a: String, version_b: String) -> bool {
-    let parts_a: Vec<u32> = version_a
-        .split('.')
-        .map(|s| s.parse::<u32>().unwrap_or(0))
-        .collect();
-    let parts_b: Vec<u32> = version_b
-        .split('.')
-        .map(|s| s.parse::<u32>().unwrap_or(0))
-        .collect();
+//noinspection D
+///
+/// Checks whether `provided_version` satisfies `required_version` (see `VersionReq` for the syntax).
+///
+/// # Arguments
+/// * `required_version` - The version requirement, such as `^1.2.3`.
+/// * `provided_version` - The version to check against the requirement.
+/// # Returns
+/// * `true` if it is satisfied, `false` otherwise or if either does not parse.
+///   Versions from the solution are validated when it is parsed, so they always do.
+///
+pub fn version_check(required_version: &str, provided_version: &str) -> bool {
+    match (required_version.parse::<VersionReq>(), provided_version.parse::<Version>()) {
+        (Ok(req), Ok(version)) => req.matches(&version),
+        _ => false,
+    }
+}
+
+/// Like `version_check`, for a `provided_version` reported by an external tool (see `Version::parse_lenient`).
+pub fn external_version_check(required_version: &str, provided_version: &str) -> bool {
+    match (required_version.parse::<VersionReq>(), Version::parse_lenient(provided_version)) {
+        (Ok(req), Ok(version)) => req.matches(&version),
+        _ => false,
+    }
+}
+
+// Splits `1.2.3-beta.1+build` into its core, pre-release and build metadata.
+fn split_version(s: &str) -> Result<(&str, Vec<Prerelease>, Vec<String>), String> {
+    let (rest, build) = match s.split_once('+') {
+        Some((rest, build)) => (rest, parse_identifiers(build, s)?),
+        None => (s, Vec::new()),
+    };
+    let (core, pre) = match rest.split_once('-') {
+        Some((core, pre)) => {
+            let pre = parse_identifiers(pre, s)?
+                .into_iter()
+                .map(|id| match id.parse::<u64>() {
+                    Ok(n) if !has_leading_zero(&id) => Ok(Prerelease::Numeric(n)),
+                    Ok(_) => Err(format!("`{}' is not a version: numeric identifier `{}' has a leading zero", s, id)),
+                    Err(_) if id.chars().all(|c| c.is_ascii_digit()) => Err(format!("`{}' is not a version: `{}' is too large", s, id)),
+                    Err(_) => Ok(Prerelease::Alphanumeric(id)),
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            (core, pre)
+        }
+        None => (rest, Vec::new()),
+    };
+    Ok((core, pre, build))
+}
 
-    // Not really elegant but it works
-    let a0 = *parts_a.get(0).unwrap_or(&0);
-    let a1 = *parts_a.get(1).unwrap_or(&0);
-    let a2 = *parts_a.get(2).unwrap_or(&0);
+// Dot-separated identifiers of a pre-release or build metadata: non-empty, `[0-9A-Za-z-]` only.
+fn parse_identifiers(s: &str, version: &str) -> Result<Vec<String>, String> {
+    s.split('.')
+        .map(|id| {
+            if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                Err(format!("`{}' is not a version: invalid identifier `{}'", version, id))
+            } else {
+                Ok(id.to_string())
+            }
+        })
+        .collect()
+}
 
-    let b0 = *parts_b.get(0).unwrap_or(&0);
-    let b1 = *parts_b.get(1).unwrap_or(&0);
-    let b2 = *parts_b.get(2).unwrap_or(&0);
+fn parse_number(part: &str, version: &str) -> Result<u64, String> {
+    if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("`{}' is not a version: `{}' is not a number", version, part));
+    }
+    if has_leading_zero(part) {
+        return Err(format!("`{}' is not a version: `{}' has a leading zero", version, part));
+    }
+    part.parse::<u64>().map_err(|_| format!("`{}' is not a version: `{}' is too large", version, part))
+}
 
-    (a0, a1, a2) > (b0, b1, b2)
+fn has_leading_zero(number: &str) -> bool {
+    number.len() > 1 && number.starts_with('0')
 }
 
 #[cfg(test)]
 mod tests {
-    use super::version_check;
+    use super::{external_version_check, version_check, Version, VersionReq};
 
     #[test]
     fn exact_match_without_modifier() {
         assert!(version_check("1.2.3", "1.2.3"));
         assert!(!version_check("1.2.3", "1.2.4"));
+        assert!(version_check("=1.2.3", "1.2.3+build.7"));
     }
 
     #[test]
     fn caret_compatible_same_major() {
-        assert!(version_check("^1.2.3", "1.0.0"));
+        assert!(!version_check("^1.2.3", "1.0.0"));
+        assert!(version_check("^1.2.3", "1.2.3"));
         assert!(version_check("^1.2.3", "1.999.999"));
         assert!(!version_check("^1.2.3", "2.0.0"));
     }
 
+    #[test]
+    fn caret_zero_major() {
+        assert!(version_check("^0.2.1", "0.2.9"));
+        assert!(!version_check("^0.2.1", "0.3.0"));
+        assert!(!version_check("^0.2.1", "0.9.0"));
+        assert!(version_check("^0.0.3", "0.0.3"));
+        assert!(!version_check("^0.0.3", "0.0.4"));
+        assert!(version_check("^0", "0.9.0"));
+    }
+
     #[test]
     fn tilde_approximately_same_major_and_minor() {
-        assert!(version_check("~1.2.3", "1.2.0"));
+        assert!(!version_check("~1.2.3", "1.2.0"));
         assert!(version_check("~1.2.3", "1.2.999"));
         assert!(!version_check("~1.2.3", "1.3.0"));
         assert!(!version_check("~1.2.3", "2.2.3"));
+        assert!(version_check("~1", "1.9.0"));
     }
 
     #[test]
@@ -175,9 +438,101 @@ mod tests {
 
     #[test]
     fn missing_majors_minors() {
-        // greater_than() fills out missing parts with 0s
+        // Missing parts count as 0s
         assert!(!version_check(">1", "0.9.9"));
         assert!(!version_check(">1", "1.0.0"));
         assert!(version_check(">1", "1.0.1"));
     }
+
+    #[test]
+    fn prerelease_precedence() {
+        // Example from the SemVer 2.0 specification
+        let ordered = ["1.0.0-alpha", "1.0.0-alpha.1", "1.0.0-alpha.beta", "1.0.0-beta", "1.0.0-beta.2", "1.0.0-beta.11", "1.0.0-rc.1", "1.0.0"];
+        let versions: Vec<Version> = ordered.iter().map(|v| v.parse().unwrap()).collect();
+        assert!(versions.windows(2).all(|w| w[0] < w[1]));
+
+        assert_eq!("1.0.0+a".parse::<Version>(), "1.0.0+b".parse::<Version>());
+        assert_eq!("1.2.3-rc.1+build.5".parse::<Version>().unwrap().to_string(), "1.2.3-rc.1+build.5");
+    }
+
+    #[test]
+    fn prereleases_only_match_prerelease_requirements() {
+        assert!(!version_check("^1.2.3", "2.0.0-alpha"));
+        assert!(!version_check(">=1.0.0", "1.3.0-beta"));
+        assert!(version_check(">=1.2.3-alpha", "1.2.3-beta"));
+        assert!(!version_check(">=1.2.3-alpha", "1.3.0-beta"));
+        assert!(version_check(">=1.2.3-alpha", "1.3.0"));
+    }
+
+    #[test]
+    fn comma_separated_ranges() {
+        assert!(version_check(">=1.2, <2.0", "1.2.0"));
+        assert!(version_check(">=1.2, <2.0", "1.9.9"));
+        assert!(!version_check(">=1.2, <2.0", "1.1.9"));
+        assert!(!version_check(">=1.2, <2.0", "2.0.0"));
+        assert!(version_check(">1.0.0,<=1.4.2,^1.3", "1.4.2"));
+        assert!(!version_check(">1.0.0,<=1.4.2,^1.3", "1.2.0"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(version_check("1.4.*", "1.4.0"));
+        assert!(version_check("1.4.x", "1.4.17"));
+        assert!(!version_check("1.4.*", "1.5.0"));
+        assert!(!version_check("1.4.*", "1.3.9"));
+        assert!(version_check("=1.*", "1.99.0"));
+        assert!(!version_check("1.X", "2.0.0"));
+        assert!(version_check("*", "0.0.1"));
+        assert!(version_check("*", "42.0.0"));
+        assert!(!version_check("*", "1.0.0-beta"));
+        assert!(version_check("1.*, <1.5", "1.4.9"));
+    }
+
+    #[test]
+    fn alternatives() {
+        assert!(version_check("^1.0 || ^2.0", "1.7.0"));
+        assert!(version_check("^1.0 || ^2.0", "2.3.1"));
+        assert!(!version_check("^1.0 || ^2.0", "3.0.0"));
+        assert!(!version_check("^1.0 || ^2.0", "0.9.0"));
+        assert!(version_check("<1.0.0 || >=1.2, <1.3 || 2.0.0", "1.2.5"));
+        assert!(version_check("<1.0.0 || >=1.2, <1.3 || 2.0.0", "2.0.0"));
+        assert!(!version_check("<1.0.0 || >=1.2, <1.3 || 2.0.0", "1.1.0"));
+        // Pre-releases are only accepted by the alternative naming them
+        assert!(version_check("^1.0 || >=2.0.0-rc.1, <2.0.0", "2.0.0-rc.2"));
+        assert!(!version_check("^1.0 || >=2.0.0-rc.1", "2.1.0-rc.1"));
+    }
+
+    #[test]
+    fn invalid_versions_are_errors() {
+        for invalid in ["abc", "1.2", "1.2.3.4", "01.2.3", "1.2.3-", "1.2.3-beta..1", "1.2.3-01", "1.2.x", ""] {
+            assert!(invalid.parse::<Version>().is_err(), "{} should not parse", invalid);
+        }
+        for invalid in ["", ">=", "^abc", "~1.2.3.4", ">=1.2-beta", ">=1.0,", "^1 ||", "1.*.3", ">=1.*", "*.1", "1.2.*-beta", "1.2 2.0"] {
+            assert!(invalid.parse::<VersionReq>().is_err(), "{} should not parse", invalid);
+        }
+        assert!(!version_check("^1.0.0", "abc"));
+    }
+
+    #[test]
+    fn external_versions_are_parsed_leniently() {
+        assert_eq!(Version::parse_lenient("3.11").unwrap(), Version::new(3, 11, 0));
+        assert_eq!(Version::parse_lenient("72").unwrap(), Version::new(72, 0, 0));
+        assert_eq!(Version::parse_lenient("2023.01.05").unwrap(), Version::new(2023, 1, 5));
+        let z3 = Version::parse_lenient("4.8.12.0").unwrap();
+        assert_eq!(z3, Version::new(4, 8, 12));
+        assert_eq!(z3.build, vec!["0"]);
+        assert!(Version::parse_lenient("1.2-rc.1").unwrap().is_prerelease());
+        for invalid in ["", "abc", "1..2", "1.2.x", "1.2.3-"] {
+            assert!(Version::parse_lenient(invalid).is_err(), "{} should not parse", invalid);
+        }
+
+        assert!(external_version_check("*", "3.11"));
+        assert!(external_version_check("^1.15", "1.15"));
+        assert!(external_version_check(">=5.40", "5.44"));
+        assert!(external_version_check("^72.1", "72.1"));
+        assert!(external_version_check("~4.8.12", "4.8.12.0"));
+        assert!(!external_version_check("^1.16", "1.15"));
+        // Versions written in the solution stay strict
+        assert!(!version_check("*", "3.11"));
+    }
 }
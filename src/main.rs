@@ -1,5 +1,8 @@
 mod solution;
 mod config_parser;
+mod build_state;
+#[cfg(test)]
+mod test_fixtures;
 
 mod compiler_interfaces {
     pub mod common {
@@ -11,6 +14,15 @@ mod compiler_interfaces {
     pub mod gcc {
         include!("compiler_interfaces/gcc.rs");
     }
+    pub mod clang {
+        include!("compiler_interfaces/clang.rs");
+    }
+    pub mod mingw {
+        include!("compiler_interfaces/mingw.rs");
+    }
+    pub mod toolchain {
+        include!("compiler_interfaces/toolchain.rs");
+    }
 }
 
 mod helpers {
@@ -25,40 +37,223 @@ mod helpers {
     pub mod file_tools {
         include!("helpers/file_tools.rs");
     }
+
+    pub mod depfile_tools {
+        include!("helpers/depfile_tools.rs");
+    }
+
+    pub mod job_pool {
+        include!("helpers/job_pool.rs");
+    }
 }
 
 pub mod dependency_manager {
     pub mod local_resolve {
         include!("dependency_manager/dependency_resolver.rs");
     }
+
+    pub mod build_graph {
+        include!("dependency_manager/build_graph.rs");
+    }
+
+    pub mod pkg_config {
+        include!("dependency_manager/pkg_config.rs");
+    }
 }
 
+use std::collections::HashMap;
 use std::env;
-use std::path::{PathBuf};
-use clap::Parser;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use clap::{Parser, Subcommand};
 
 use crate::helpers::console::Console;
+use crate::helpers::job_pool::JobPool;
 
 use crate::config_parser::{parse_config};
 
-use crate::compiler_interfaces::common::Compiler;
-use crate::dependency_manager::local_resolve::{has_circular_dependency, resolve_project_build_inputs};
-use crate::solution::Solution;
-
-
+use crate::compiler_interfaces::clang::ClangCompiler;
+use crate::build_state::{clean_output_dir, recorded_output_dirs, BuildState};
+use crate::compiler_interfaces::common::{project_output_dir, write_compile_commands, Compiler, CompilerKind, Compilers};
+use crate::compiler_interfaces::gcc::GccCompiler;
+use crate::compiler_interfaces::mingw::MingwCompiler;
+use crate::compiler_interfaces::msvc::MsvcCompiler;
+use crate::compiler_interfaces::toolchain::discover_toolchain;
+use crate::dependency_manager::build_graph::BuildGraph;
+use crate::dependency_manager::local_resolve::{check_local_dependencies, check_project_names, resolve_local_dependencies_in_order, select_projects};
+use crate::dependency_manager::pkg_config::resolve_external_dependencies;
+use crate::helpers::version_tools::version_check;
+use crate::solution::{BuildProfile, Project, ProjectType, Solution, TargetArch, TargetPlatform};
+
+
+// Options are global so they can be given before or after the command.
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    #[arg(short, long, help = "Path to the solution configuration file")]
-    solution_path: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(short, long, global = true, help = "Path to the solution configuration file, or its directory (defaults to ./spbuild.json)")]
+    solution_path: Option<String>,
 
-    #[arg(short, long, action = clap::ArgAction::SetTrue, help = "Enable verbose output")]
+    #[arg(short, long, global = true, action = clap::ArgAction::SetTrue, help = "Enable verbose output")]
     verbose: bool,
+
+    #[arg(short, long, global = true, default_value_t = JobPool::default_jobs(), help = "Number of files to compile in parallel")]
+    jobs: usize,
+
+    #[arg(short, long, global = true, action = clap::ArgAction::SetTrue, help = "Keep compiling the remaining files after an error")]
+    keep_going: bool,
+
+    #[arg(short, long, global = true, value_enum, help = "Compiler backend to use (overrides the solution's `compiler`)")]
+    compiler: Option<CompilerKind>,
+
+    #[arg(long, global = true, action = clap::ArgAction::SetTrue, help = "Link with lld (Clang only)")]
+    lld: bool,
+
+    #[arg(short, long, global = true, value_enum, help = "Platform to build for (defaults to the host platform)")]
+    target_platform: Option<TargetPlatform>,
+
+    #[arg(short, long, global = true, default_value = "debug", help = "Build profile: debug, release or one defined in the solution")]
+    profile: String,
+
+    #[arg(long, global = true, help = "Only work on this project (and, when building, its dependencies). Can be repeated")]
+    project: Vec<String>,
+
+    #[arg(long, global = true, help = "Do not work on this project. Can be repeated")]
+    exclude: Vec<String>,
+
+    #[arg(long, global = true, action = clap::ArgAction::SetTrue, help = "Also write compile_commands.json at the solution root when building")]
+    compile_commands: bool,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+enum Command {
+    /// Build the solution (what spbuild does without a command)
+    Build,
+    /// Remove what the build produced, and nothing else
+    Clean,
+    /// Build and run an executable project
+    Run {
+        #[arg(long, action = clap::ArgAction::SetTrue, help = "Run from the solution root instead of the current directory")]
+        in_solution_root: bool,
+
+        #[arg(last = true, help = "Arguments passed to the program, after `--`")]
+        program_args: Vec<String>,
+    },
+    /// Build and run the test executables
+    Test {
+        #[arg(long, action = clap::ArgAction::SetTrue, help = "Run from the solution root instead of the current directory")]
+        in_solution_root: bool,
+
+        #[arg(last = true, help = "Arguments passed to every test, after `--`")]
+        program_args: Vec<String>,
+    },
+    /// Print the dependencies between the projects of the solution
+    Graph,
+    /// Check the solution and its dependencies without building anything
+    Check,
+    /// Write compile_commands.json at the solution root without compiling anything
+    CompileCommands,
+}
+
+/// A parsed solution and what the command line selected to work on it.
+struct Session {
+    /// Absolute path to the solution configuration file; its directory is the solution root.
+    config_path: PathBuf,
+    solution: Solution,
+    target_platform: TargetPlatform,
+    profile: BuildProfile,
 }
 
 
+///
+/// Creates the compiler backend selected on the command line or in the solution for `platform` and `arch`
+/// (MSVC by default on Windows, GCC elsewhere).
+/// GCC builds for Windows from another platform with MinGW-w64.
+///
+fn create_compiler(args: &Args, solution: &Solution, profile: &BuildProfile, platform: TargetPlatform, arch: TargetArch, job_pool: Arc<JobPool>) -> Result<Box<dyn Compiler + Sync>, String> {
+    let default_kind = if TargetPlatform::host() == Some(TargetPlatform::Windows) { CompilerKind::Msvc } else { CompilerKind::Gcc };
+    let kind = args.compiler.or(solution.compiler).unwrap_or(default_kind);
+    let use_lld = args.lld || solution.use_lld;
+    let toolchain = || discover_toolchain(kind, platform, arch, &solution.toolchain, args.verbose);
+
+    if use_lld && kind != CompilerKind::Clang {
+        Console::log_warning("lld is only supported with the Clang backend; using the default linker");
+    }
+
+    match kind {
+        CompilerKind::Gcc if platform == TargetPlatform::Windows => Ok(Box::new(MingwCompiler::new(toolchain()?, arch, profile.clone(), job_pool))),
+        CompilerKind::Gcc => {
+            let toolchain = toolchain()?;
+            Ok(Box::new(GccCompiler {
+                gcc_path: toolchain.cc,
+                gcc_args: toolchain.cc_args,
+                gpp_path: toolchain.cxx,
+                gpp_args: toolchain.cxx_args,
+                ar_path: toolchain.ar,
+                linker: None,
+                platform,
+                arch,
+                target_flags: toolchain.target_flags,
+                profile: profile.clone(),
+                job_pool,
+            }))
+        }
+        CompilerKind::Clang => Ok(Box::new(ClangCompiler::new(toolchain()?, platform, arch, use_lld, profile.clone(), job_pool))),
+        CompilerKind::Msvc if platform != TargetPlatform::Windows => {
+            Err(format!("MSVC cannot build for {}; choose another compiler with --compiler", platform.name()))
+        }
+        CompilerKind::Msvc => Ok(Box::new(MsvcCompiler::discover(&solution.toolchain, arch, profile.clone(), job_pool, args.verbose)?)),
+    }
+}
+
+
+///
+/// Resolves the graph of `projects` (every project if empty) and their dependencies for `target_platform`, except the
+/// `--exclude`d ones, and creates a compiler for each of its architectures.
+/// Returns why if either fails, including when a name is not a project of the solution.
+///
+fn prepare_build(args: &Args, solution_root: &Path, solution: &Solution, target_platform: TargetPlatform, profile: &BuildProfile, projects: &[String]) -> Result<(BuildGraph, Compilers, Arc<JobPool>), String> {
+    let job_pool = Arc::new(JobPool::new(args.jobs, args.keep_going));
+    Console::log_verbose(format!("Running up to {} jobs in parallel", job_pool.jobs()).as_str(), args.verbose);
+
+    let selected = select_projects(solution, projects, &args.exclude).map_err(|e| format!("Error selecting projects: {}", e))?;
+
+    // Resolve dependencies and include dirs of every selected project.
+    let graph = BuildGraph::from_solution(solution, solution_root, target_platform, &selected, args.verbose)
+        .map_err(|e| format!("Error resolving dependencies: {}", e))?;
+
+    // One compiler per architecture, each with its own toolchain and flags.
+    let mut compilers: Compilers = HashMap::new();
+    for arch in graph.archs() {
+        compilers.insert(arch, create_compiler(args, solution, profile, target_platform, arch, job_pool.clone())?);
+    }
+
+    Ok((graph, compilers, job_pool))
+}
+
+/// Writes `compile_commands.json` at the solution root for every source file of `graph`. Returns whether it was written.
+fn export_compile_commands(graph: &BuildGraph, compilers: &Compilers, solution_root: &Path) -> bool {
+    match graph.compile_commands(compilers, solution_root).and_then(|commands| {
+        write_compile_commands(solution_root, &commands).map(|path| (path, commands.len()))
+    }) {
+        Ok((path, count)) => {
+            Console::log_success(format!("Wrote {} compile command(s) to {}", count, path.display()).as_str());
+            true
+        }
+        Err(e) => {
+            Console::log_fatal(format!("Error exporting compile commands: {}", e).as_str());
+            false
+        }
+    }
+}
+
 //noinspection D
-fn linux_build(args: Args, config_path: PathBuf, solution: Solution) {
+/// Builds `projects` (every project if empty) and their dependencies for `target_platform`, except the `--exclude`d ones.
+/// With `--compile-commands`, `compile_commands.json` is written before compiling.
+/// Returns whether the build succeeded.
+fn build_solution(args: &Args, config_path: &Path, solution: &Solution, target_platform: TargetPlatform, profile: &BuildProfile, projects: &[String]) -> bool {
     let working_dir = config_path
         .parent()
         .expect("Config path has no parent")
@@ -66,110 +261,193 @@ fn linux_build(args: Args, config_path: PathBuf, solution: Solution) {
 
     Console::log_success(format!("Successfully parsed solution: {}", solution.name).as_str());
 
-    // Track what we've already compiled to avoid rebuilding the same dependency multiple times.
-    let mut compiled_projects: Vec<String> = Vec::new();
+    let (graph, compilers, job_pool) = match prepare_build(args, &working_dir, solution, target_platform, profile, projects) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            Console::log_fatal(e.as_str());
+            Console::log_fatal("==== Aborting build ====");
+            return false;
+        }
+    };
 
-    for project in &solution.projects {
-        let compiler = compiler_interfaces::gcc::GccCompiler {
-            gcc_path: compiler_interfaces::gcc::GccCompiler::detect_compiler_path().unwrap(),
-            gpp_path: compiler_interfaces::gcc::GccCompiler::detect_gpp_path().unwrap(),
-        };
+    if args.compile_commands && !export_compile_commands(&graph, &compilers, &working_dir) {
+        Console::log_fatal("==== Aborting build ====");
+        return false;
+    }
 
-        // Resolve dependencies and include dirs.
-        let inputs = match resolve_project_build_inputs(project, &solution, &working_dir, args.verbose) {
-            Ok(v) => v,
-            Err(e) => {
-                Console::log_fatal(format!("Error resolving dependencies: {}", e).as_str());
-                return;
-            }
-        };
+    if let Err(e) = graph.build(&compilers, solution, &working_dir, &job_pool, args.verbose) {
+        Console::log_fatal(format!("Error building solution: {}", e).as_str());
+        Console::log_fatal("==== Aborting build ====");
+        false
+    } else {
+        Console::log_success("=== Solution built successfully ===");
+        true
+    }
+}
 
-        // Build local deps first.
-        for dep in &inputs.local_deps_in_order {
-            if compiled_projects.iter().any(|n| n == &dep.name) {
-                continue;
-            }
+///
+/// Finds the executable project `spbuild run` runs: the one named `name`, or the only executable of the solution
+/// that is neither a test nor in `exclude`.
+/// It must be built for `target_platform` and the host's architecture, and `target_platform` must be the host's.
+/// # Returns
+/// * The project and the architecture to run it for, or why it cannot be run.
+///
+fn find_run_target(solution: &Solution, name: Option<&str>, exclude: &[String], target_platform: TargetPlatform) -> Result<(Project, TargetArch), String> {
+    if TargetPlatform::host() != Some(target_platform) {
+        return Err(format!("Cannot run programs built for {} on this host", target_platform.name()));
+    }
 
-            let res = compiler.compile_project(
-                dep,
-                &solution,
-                &working_dir,
-                Vec::new(),
-                args.verbose,
-            );
-
-            if let Err(e) = res {
-                Console::log_fatal(format!("Error compiling dependency {}: {}", dep.name, e).as_str());
-                return;
+    let executables: Vec<&Project> = solution.projects
+        .iter()
+        .filter(|p| p.project_type == ProjectType::Executable && !p.test && p.targets_platform(target_platform) && !exclude.contains(&p.name))
+        .collect();
+
+    let project = match name {
+        Some(name) => match solution.projects.iter().find(|p| p.name == name) {
+            Some(p) if p.project_type != ProjectType::Executable => return Err(format!("Project {} is not an executable", name)),
+            Some(p) if !p.targets_platform(target_platform) => return Err(format!("Project {} is not built for {}", name, target_platform.name())),
+            Some(p) => p,
+            None => return Err(format!("Unknown project: {}", name)),
+        },
+        None => match executables.as_slice() {
+            [project] => project,
+            [] => return Err(format!("The solution has no executable project for {}", target_platform.name())),
+            _ => {
+                let names: Vec<&str> = executables.iter().map(|p| p.name.as_str()).collect();
+                return Err(format!("The solution has several executable projects, choose one with --project: {}", names.join(", ")));
             }
+        },
+    };
 
-            let res = compiler.link_project(
-                dep,
-                &solution,
-                &working_dir,
-                Vec::new(),
-                args.verbose,
-            );
-
-            if let Err(e) = res {
-                Console::log_fatal(format!("Error linking dependency {}: {}\n", dep.name, e).as_str());
-                return;
-            }
+    match TargetArch::host() {
+        Some(arch) if project.target_archs.contains(&arch) => Ok((project.clone(), arch)),
+        _ => Err(format!("Project {} is not built for the host architecture ({})", project.name, env::consts::ARCH)),
+    }
+}
 
-            compiled_projects.push(dep.name.clone());
-        }
+///
+/// Finds the test executables `spbuild test` runs: the tests among `projects` (every project if empty) but the
+/// `exclude`d ones, built for `target_platform` and the host's architecture. `target_platform` must be the host's.
+/// # Returns
+/// * The tests and the architecture to run them for, or why there is nothing to run.
+///
+fn find_test_targets(solution: &Solution, projects: &[String], exclude: &[String], target_platform: TargetPlatform) -> Result<(Vec<Project>, TargetArch), String> {
+    if TargetPlatform::host() != Some(target_platform) {
+        return Err(format!("Cannot run programs built for {} on this host", target_platform.name()));
+    }
+    let arch = TargetArch::host().ok_or_else(|| format!("Unsupported host architecture: {}", env::consts::ARCH))?;
 
-        // Compile current project with resolved include dirs.
-        let res = compiler.compile_project(
-            &project,
-            &solution,
-            &working_dir,
-            inputs.include_dirs.clone(),
-            args.verbose,
-        );
+    if let Some(name) = projects.iter().find(|name| !solution.projects.iter().any(|p| p.name == **name && p.test)) {
+        return Err(format!("Project {} is not a test of the solution", name));
+    }
 
-        if let Err(e) = res {
-            Console::log_fatal(format!("Error compiling project: {}", e).as_str());
-            return;
-        } else {
-            Console::log_success("=== Project compiled successfully ===");
-        }
+    let tests: Vec<Project> = solution.projects
+        .iter()
+        .filter(|p| p.test && (projects.is_empty() || projects.contains(&p.name)) && !exclude.contains(&p.name))
+        .filter(|p| p.targets_platform(target_platform) && p.target_archs.contains(&arch))
+        .cloned()
+        .collect();
 
-        // Link current project.
-        let mut link_inputs = inputs.dep_output_dirs.clone();
-        // Keep the project's include dirs around as well in case the linker needs them later (e.g. for -L/-l).
-        // For now, gcc.rs interprets these as directories to scan for `.o` files.
-        link_inputs.extend(inputs.include_dirs.clone());
+    if tests.is_empty() {
+        return Err(format!("The solution has no test built for {} {}", target_platform.name(), arch.name()));
+    }
+    Ok((tests, arch))
+}
 
-        let res = compiler.link_project(
-            &project,
-            &solution,
-            &working_dir,
-            link_inputs,
-            args.verbose,
-        );
+///
+/// Runs the executable built for `project`, with the output directories of its dynamic library dependencies
+/// in the library search path (`LD_LIBRARY_PATH`, or `PATH` on Windows).
+/// # Arguments
+/// * `solution` - The solution containing all projects.
+/// * `solution_root` - The root path of the solution.
+/// * `project` - The executable project, already built.
+/// * `arch` - The architecture it was built for.
+/// * `profile` - The profile it was built with.
+/// * `working_dir` - The directory to run it from.
+/// * `program_args` - The arguments passed to the program.
+/// # Returns
+/// * The program's exit code, or why it could not be started.
+///
+#[allow(clippy::too_many_arguments)]
+fn run_executable(solution: &Solution, solution_root: &Path, project: &Project, arch: TargetArch, profile: &BuildProfile, working_dir: &Path, program_args: &[String], verbose: bool) -> Result<i32, String> {
+    let platform = TargetPlatform::host().ok_or("Unsupported host platform")?;
+    let output_dir = project_output_dir(solution_root, project, &profile.name, platform, arch);
+
+    // The link record knows where each backend put the executable.
+    let Some(link) = BuildState::load(&output_dir).link else {
+        return Err(format!("Project {} has not been linked", project.name));
+    };
+
+    let library_path_var = if platform == TargetPlatform::Windows { "PATH" } else { "LD_LIBRARY_PATH" };
+    let mut library_dirs: Vec<PathBuf> = resolve_local_dependencies_in_order(project, solution)
+        .iter()
+        .filter(|d| d.project_type == ProjectType::DynamicLib)
+        .map(|d| project_output_dir(solution_root, d, &profile.name, platform, arch))
+        .collect();
+    if let Some(existing) = env::var_os(library_path_var) {
+        library_dirs.extend(env::split_paths(&existing));
+    }
+    let library_path = env::join_paths(library_dirs).map_err(|e| format!("Invalid {}: {}", library_path_var, e))?;
 
-        if let Err(e) = res {
-            Console::log_fatal(format!("Error linking project: {}\n", e).as_str());
-            return;
-        } else {
-            Console::log_success("=== Project linked successfully ===");
-        }
+    Console::log_info(format!("Running {} {}", link.output.display(), program_args.join(" ")).as_str());
+    Console::log_verbose(format!("{}={}", library_path_var, library_path.to_string_lossy()).as_str(), verbose);
 
-        compiled_projects.push(project.name.clone());
-    }
+    let status = std::process::Command::new(&link.output)
+        .args(program_args)
+        .current_dir(working_dir)
+        .env(library_path_var, library_path)
+        .status()
+        .map_err(|e| format!("Failed to run {}: {}", link.output.display(), e))?;
+
+    // Killed by a signal: there is no exit code to forward.
+    Ok(status.code().unwrap_or(1))
 }
 
 
-fn main() {
-    let args = Args::parse();
-    let mut config_path = PathBuf::from(&args.solution_path);
+///
+/// Deletes the files the build recorded as produced for `projects` (every project if empty) but the `exclude`d ones,
+/// for every profile, platform and architecture they were built for, including ones since removed from the solution.
+/// Their dependencies are left alone, and so are other files in `output/`.
+/// # Returns
+/// * Whether everything recorded could be deleted.
+///
+fn clean_solution(solution: &Solution, solution_root: &Path, projects: &[String], exclude: &[String], verbose: bool) -> bool {
+    if check_project_names(solution, projects.iter().chain(exclude)).is_err() {
+        return false;
+    }
 
-    Console::log_info("===== SPBuild Starting =====");
+    let mut removed = 0;
+    let cleaned = solution.projects
+        .iter()
+        .filter(|p| (projects.is_empty() || projects.contains(&p.name)) && !exclude.contains(&p.name));
+    for project in cleaned {
+        // The build states say where the project was built, even for profiles and architectures no longer in the solution.
+        for output_dir in recorded_output_dirs(solution_root, &project.path) {
+            match clean_output_dir(&output_dir, solution_root, verbose) {
+                Ok(count) => removed += count,
+                Err(e) => {
+                    Console::log_error(format!("Failed to clean {}: {}", output_dir.display(), e).as_str());
+                    return false;
+                }
+            }
+        }
+    }
+
+    Console::log_success(format!("Removed {} files", removed).as_str());
+    true
+}
+
+///
+/// Finds and parses the solution configuration file, and resolves the target platform and profile to use.
+/// # Returns
+/// * The session, or `None` if anything is wrong (it has been reported).
+///
+fn load_session(args: &Args) -> Option<Session> {
+    let mut config_path = PathBuf::from(args.solution_path.as_deref().unwrap_or("spbuild.json"));
 
     if !config_path.exists() {
         Console::log_fatal(format!("Configuration file not found: {}", config_path.display()).as_str());
-        return;
+        return None;
     }
 
     if !config_path.is_file() {
@@ -178,34 +456,341 @@ fn main() {
         config_path = config_path.join("spbuild.json");
     }
     else {
-        Console::log_info(format!("Using solution configuration file: {}", &args.solution_path).as_str());
+        Console::log_info(format!("Using solution configuration file: {}", config_path.display()).as_str());
     }
-    let config = parse_config(&config_path).map_err(|e| {
-        Console::log_fatal(format!("Failed to parse config: {}", e).as_str());
-        Console::log_fatal("==== Aborting build ====");
-    }).unwrap();
+
+    // The solution root is the config file's directory, which is empty for a bare file name.
+    let config_path = match config_path.canonicalize() {
+        Ok(path) => path,
+        Err(e) => {
+            Console::log_fatal(format!("Failed to open config {}: {}", config_path.display(), e).as_str());
+            return None;
+        }
+    };
+
+    let solution = match parse_config(&config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            Console::log_fatal(format!("Failed to parse config: {}", e).as_str());
+            Console::log_fatal("==== Aborting build ====");
+            return None;
+        }
+    };
 
 
     Console::log_info("Detecting platform... ");
     let current_platform = env::consts::OS;
     Console::log_info(format!("{}!", &current_platform).as_str());
 
-    Console::log_info("\n= STARTING BUILD =\n");
+    let Some(target_platform) = args.target_platform.or(TargetPlatform::host()) else {
+        Console::log_fatal(format!("Unsupported host platform: {}. Use --target-platform to choose one", current_platform).as_str());
+        Console::log_fatal("==== Aborting build ====");
+        return None;
+    };
+    Console::log_info(format!("Target platform: {}", target_platform.name()).as_str());
 
-    for project in &config.projects {
-        if has_circular_dependency(&project, &config, &mut Vec::new()) {
-            Console::log_fatal(format!("Circular dependency detected in project: {}", project.name).as_str());
-            Console::log_fatal("==== Aborting build ====");
-            return;
+    let Some(profile) = solution.profile(&args.profile) else {
+        Console::log_fatal(format!("Unknown profile: {}. Available profiles: {}", args.profile, solution.profile_names().join(", ")).as_str());
+        Console::log_fatal("==== Aborting build ====");
+        return None;
+    };
+    Console::log_info(format!("Profile: {}", profile.name).as_str());
+
+    Some(Session { config_path, solution, target_platform, profile })
+}
+
+///
+/// Checks the dependencies between projects (versions and cycles), then resolves the dependencies that come from the system.
+/// Both run so every problem is reported at once.
+/// # Returns
+/// * Whether every required dependency was resolved.
+///
+fn resolve_dependencies(args: &Args, solution: &mut Solution) -> bool {
+    let local = check_local_dependencies(solution, args.verbose);
+    let external = resolve_external_dependencies(solution, args.verbose);
+    local.is_ok() && external.is_ok()
+}
+
+/// Whether spbuild can build for `target_platform` from the host it runs on, reporting why not.
+fn can_build_for(target_platform: TargetPlatform) -> bool {
+    let current_platform = env::consts::OS;
+
+    if current_platform != "windows" && current_platform != "linux" {
+        Console::log_fatal(format!("{} platform detected. Only linux and windows hosts are supported for now.", current_platform).as_str());
+        return false;
+    }
+
+    // Linux reaches Windows by cross compiling with MinGW-w64 (or Clang).
+    if target_platform == TargetPlatform::Macos {
+        Console::log_fatal(format!("Building for {} from {} is not supported yet.", target_platform.name(), current_platform).as_str());
+        return false;
+    }
+
+    true
+}
+
+///
+/// Prints every project of the solution with the projects and external packages it depends on.
+///
+fn print_dependency_graph(solution: &Solution, target_platform: TargetPlatform) {
+    Console::log_info(format!("Solution {}:", solution.name).as_str());
+
+    for project in &solution.projects {
+        let skipped = if project.targets_platform(target_platform) { String::new() } else { format!(", not built for {}", target_platform.name()) };
+        Console::log_info(format!("{} {} ({}{})", project.name, project.version, project_type_name(project), skipped).as_str());
+
+        for dep in &project.dependencies {
+            let optional = if dep.optional { ", optional" } else { "" };
+            let source = match solution.projects.iter().find(|p| p.name == dep.name && version_check(&dep.version, &p.version)) {
+                Some(dep_project) => format!("{} {}", dep_project.name, dep_project.version),
+                None if solution.projects.iter().any(|p| p.name == dep.name) => "no matching version".to_string(),
+                None => "external".to_string(),
+            };
+            Console::log_info(format!("  -> {} {} ({}{})", dep.name, dep.version, source, optional).as_str());
         }
     }
+}
 
-    if current_platform == "windows" {
-        Console::log_fatal("Windows platform detected. MSVC support is not yet implemented.");
-        Console::log_fatal("==== Aborting build ====");
-        //TODO : Call msvc functions
+fn project_type_name(project: &Project) -> &'static str {
+    match project.project_type {
+        ProjectType::Executable => "executable",
+        ProjectType::StaticLib => "static library",
+        ProjectType::DynamicLib => "dynamic library",
     }
-    else if current_platform == "linux" {
-        linux_build(args, config_path, config);
+}
+
+fn main() {
+    let args = Args::parse();
+    let command = args.command.clone().unwrap_or(Command::Build);
+
+    Console::log_info("===== SPBuild Starting =====");
+
+    let Some(Session { config_path, mut solution, target_platform, profile }) = load_session(&args) else {
+        std::process::exit(1);
+    };
+
+    match command {
+        Command::Build => {
+            Console::log_info("\n= STARTING BUILD =\n");
+
+            if !resolve_dependencies(&args, &mut solution) || !can_build_for(target_platform) {
+                Console::log_fatal("==== Aborting build ====");
+                std::process::exit(1);
+            }
+            if !build_solution(&args, &config_path, &solution, target_platform, &profile, &args.project) {
+                std::process::exit(1);
+            }
+        }
+        Command::Run { in_solution_root, program_args } => {
+            let solution_root = config_path.parent().expect("Config path has no parent");
+
+            if args.project.len() > 1 {
+                Console::log_fatal("`spbuild run` runs a single project: give at most one --project");
+                std::process::exit(1);
+            }
+
+            let (project, arch) = match find_run_target(&solution, args.project.first().map(String::as_str), &args.exclude, target_platform) {
+                Ok(target) => target,
+                Err(e) => {
+                    Console::log_fatal(e.as_str());
+                    std::process::exit(1);
+                }
+            };
+
+            if !resolve_dependencies(&args, &mut solution)
+                || !can_build_for(target_platform)
+                || !build_solution(&args, &config_path, &solution, target_platform, &profile, std::slice::from_ref(&project.name)) {
+                std::process::exit(1);
+            }
+
+            let working_dir = if in_solution_root { solution_root.to_path_buf() } else { PathBuf::from(".") };
+            match run_executable(&solution, solution_root, &project, arch, &profile, &working_dir, &program_args, args.verbose) {
+                Ok(code) => std::process::exit(code),
+                Err(e) => {
+                    Console::log_fatal(e.as_str());
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Test { in_solution_root, program_args } => {
+            let solution_root = config_path.parent().expect("Config path has no parent");
+
+            let (tests, arch) = match find_test_targets(&solution, &args.project, &args.exclude, target_platform) {
+                Ok(targets) => targets,
+                Err(e) => {
+                    Console::log_fatal(e.as_str());
+                    std::process::exit(1);
+                }
+            };
+
+            let names: Vec<String> = tests.iter().map(|t| t.name.clone()).collect();
+            if !resolve_dependencies(&args, &mut solution)
+                || !can_build_for(target_platform)
+                || !build_solution(&args, &config_path, &solution, target_platform, &profile, &names) {
+                std::process::exit(1);
+            }
+
+            // Every test runs, even after one failed, so they are all reported.
+            let working_dir = if in_solution_root { solution_root.to_path_buf() } else { PathBuf::from(".") };
+            let mut failed: Vec<&str> = Vec::new();
+            for test in &tests {
+                match run_executable(&solution, solution_root, test, arch, &profile, &working_dir, &program_args, args.verbose) {
+                    Ok(0) => Console::log_success(format!("Test {} passed", test.name).as_str()),
+                    Ok(code) => {
+                        Console::log_error(format!("Test {} failed with exit code {}", test.name, code).as_str());
+                        failed.push(&test.name);
+                    }
+                    Err(e) => {
+                        Console::log_error(e.as_str());
+                        failed.push(&test.name);
+                    }
+                }
+            }
+
+            if !failed.is_empty() {
+                Console::log_fatal(format!("{} of {} tests failed: {}", failed.len(), tests.len(), failed.join(", ")).as_str());
+                std::process::exit(1);
+            }
+            Console::log_success(format!("All {} tests passed", tests.len()).as_str());
+        }
+        Command::Check => {
+            let solution_root = config_path.parent().expect("Config path has no parent");
+
+            if !resolve_dependencies(&args, &mut solution)
+                || select_projects(&solution, &args.project, &args.exclude)
+                    .and_then(|selected| BuildGraph::from_solution(&solution, solution_root, target_platform, &selected, args.verbose))
+                    .is_err() {
+                Console::log_fatal(format!("Solution {} has errors", solution.name).as_str());
+                std::process::exit(1);
+            }
+            Console::log_success(format!("Solution {} is valid", solution.name).as_str());
+        }
+        Command::Graph => {
+            // External packages are not looked up: the graph only shows what the solution asks for.
+            if check_local_dependencies(&mut solution, args.verbose).is_err() {
+                Console::log_fatal("==== Aborting ====");
+                std::process::exit(1);
+            }
+            print_dependency_graph(&solution, target_platform);
+        }
+        Command::Clean => {
+            let solution_root = config_path.parent().expect("Config path has no parent");
+            if !clean_solution(&solution, solution_root, &args.project, &args.exclude, args.verbose) {
+                std::process::exit(1);
+            }
+        }
+        Command::CompileCommands => {
+            let solution_root = config_path.parent().expect("Config path has no parent");
+
+            if !resolve_dependencies(&args, &mut solution) || !can_build_for(target_platform) {
+                Console::log_fatal("==== Aborting ====");
+                std::process::exit(1);
+            }
+            let exported = match prepare_build(&args, solution_root, &solution, target_platform, &profile, &args.project) {
+                Ok((graph, compilers, _)) => export_compile_commands(&graph, &compilers, solution_root),
+                Err(e) => {
+                    Console::log_fatal(e.as_str());
+                    false
+                }
+            };
+            if !exported {
+                Console::log_fatal("==== Aborting ====");
+                std::process::exit(1);
+            }
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solution::{Project, ProjectType, Solution, TargetArch, TargetPlatform};
+    use crate::test_fixtures::{project, solution};
+    use super::{find_run_target, find_test_targets};
+
+    fn host() -> (TargetPlatform, TargetArch) {
+        (TargetPlatform::host().unwrap(), TargetArch::host().unwrap())
+    }
+
+    // An architecture other than the host's.
+    fn other_arch() -> TargetArch {
+        if host().1 == TargetArch::ARM64 { TargetArch::X64 } else { TargetArch::ARM64 }
+    }
+
+    // A solution whose projects are built for the host's architecture.
+    fn host_solution(mut projects: Vec<Project>) -> Solution {
+        for project in &mut projects {
+            project.target_archs = vec![host().1];
+        }
+        solution(projects)
+    }
+
+    fn test_project(name: &str) -> Project {
+        let mut test = project(name, ProjectType::Executable, &["lib"]);
+        test.test = true;
+        test
+    }
+
+    fn names(projects: &[Project]) -> Vec<&str> {
+        projects.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_targets_are_the_selected_tests_built_for_the_host() {
+        let (platform, arch) = host();
+        let mut sol = host_solution(vec![
+            project("lib", ProjectType::StaticLib, &[]),
+            project("app", ProjectType::Executable, &["lib"]),
+            test_project("unit"),
+            test_project("integration"),
+            test_project("cross"),
+        ]);
+        sol.projects[4].target_archs = vec![other_arch()];
+        let select = |projects: &[&str], exclude: &[&str]| {
+            let projects: Vec<String> = projects.iter().map(|n| n.to_string()).collect();
+            let exclude: Vec<String> = exclude.iter().map(|n| n.to_string()).collect();
+            find_test_targets(&sol, &projects, &exclude, platform)
+        };
+
+        let (tests, test_arch) = select(&[], &[]).unwrap();
+        assert_eq!((names(&tests), test_arch), (vec!["unit", "integration"], arch));
+        assert_eq!(names(&select(&["integration"], &[]).unwrap().0), ["integration"]);
+        assert_eq!(names(&select(&[], &["unit"]).unwrap().0), ["integration"]);
+
+        assert!(select(&["app"], &[]).is_err());
+        assert!(select(&["nope"], &[]).is_err());
+        assert!(select(&["cross"], &[]).is_err());
+        assert!(select(&[], &["unit", "integration"]).is_err());
+        assert!(find_test_targets(&host_solution(vec![project("app", ProjectType::Executable, &[])]), &[], &[], platform).is_err());
+    }
+
+    #[test]
+    fn the_run_target_is_the_named_or_only_executable_built_for_the_host() {
+        let (platform, arch) = host();
+        let lib = project("lib", ProjectType::StaticLib, &[]);
+        let app = project("app", ProjectType::Executable, &["lib"]);
+        let tool = project("tool", ProjectType::Executable, &[]);
+
+        // The only executable, tests aside
+        let sol = host_solution(vec![lib.clone(), app.clone(), test_project("unit")]);
+        assert_eq!(find_run_target(&sol, None, &[], platform).map(|(p, a)| (p.name, a)), Ok(("app".to_string(), arch)));
+        assert_eq!(find_run_target(&sol, Some("app"), &[], platform).map(|(p, _)| p.name), Ok("app".to_string()));
+        assert!(find_run_target(&sol, Some("lib"), &[], platform).err().unwrap().contains("not an executable"));
+        assert!(find_run_target(&sol, Some("nope"), &[], platform).err().unwrap().contains("Unknown project"));
+
+        // Several executables need a name, unless all but one are excluded
+        let sol = host_solution(vec![lib.clone(), app.clone(), tool.clone()]);
+        assert!(find_run_target(&sol, None, &[], platform).err().unwrap().contains("app, tool"));
+        assert_eq!(find_run_target(&sol, None, &["tool".to_string()], platform).map(|(p, _)| p.name), Ok("app".to_string()));
+        assert!(find_run_target(&sol, None, &["app".to_string(), "tool".to_string()], platform).err().unwrap().contains("no executable"));
+        assert!(find_run_target(&host_solution(vec![lib.clone()]), None, &[], platform).err().unwrap().contains("no executable"));
+
+        // Platforms and architectures the host cannot run
+        let mut sol = host_solution(vec![lib, app, tool]);
+        let other_platform = if platform == TargetPlatform::Windows { TargetPlatform::Linux } else { TargetPlatform::Windows };
+        sol.projects[1].target_platforms = vec![other_platform];
+        sol.projects[2].target_archs = vec![other_arch()];
+        assert!(find_run_target(&sol, Some("app"), &[], platform).err().unwrap().contains("is not built for"));
+        assert!(find_run_target(&sol, Some("tool"), &[], platform).err().unwrap().contains("host architecture"));
+        assert!(find_run_target(&sol, Some("tool"), &[], other_platform).err().unwrap().contains("on this host"));
+    }
+}
@@ -1,10 +1,110 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use clap::ValueEnum;
 use serde::Deserialize;
 
+use crate::compiler_interfaces::common::CompilerKind;
+use crate::compiler_interfaces::toolchain::ToolchainConfig;
+
 #[derive(Deserialize)]
 pub struct Solution {
     pub name: String,
     pub projects: Vec<Project>,
+    /// Compiler backend to build with. Overridden by `--compiler`, defaults to GCC.
+    #[serde(default)]
+    pub compiler: Option<CompilerKind>,
+    /// Link with LLVM's lld (Clang backend only).
+    #[serde(default)]
+    pub use_lld: bool,
+    /// Explicit compiler and archiver programs. Anything not set is discovered from the environment and `PATH`.
+    #[serde(default)]
+    pub toolchain: ToolchainConfig,
+    /// Build profiles selected with `--profile`. They add to, or replace, the built-in `debug` and `release`.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, BuildProfile>,
+}
+
+impl Solution {
+    /// Returns the profile named `name`, looking at the solution's profiles before the built-in ones.
+    pub fn profile(&self, name: &str) -> Option<BuildProfile> {
+        match self.profiles.get(name) {
+            Some(profile) => Some(BuildProfile { name: name.to_string(), ..profile.clone() }),
+            None => BuildProfile::builtin(name),
+        }
+    }
+
+    /// Names of every profile that can be selected, built-in ones included.
+    pub fn profile_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = BuildProfile::BUILTIN.iter().map(|n| n.to_string()).collect();
+        for name in self.profiles.keys() {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        names
+    }
+}
+
+/// Optimisation level, translated by each backend (`-O0`/`/Od`, `-O2`/`/O2`, `-Os`/`/O1`).
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Optimization {
+    #[default]
+    None,
+    Speed,
+    Size,
+}
+
+///
+/// Settings applied to every project of a build: the built-in `debug` and `release`, or a profile
+/// from the solution's `profiles`. Each profile builds into its own `output/<profile>/` directory.
+///
+#[derive(Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct BuildProfile {
+    /// Name of the profile, taken from its key in `profiles`.
+    #[serde(skip)]
+    pub name: String,
+    #[serde(default)]
+    pub optimization: Optimization,
+    /// Whether to generate debug information (`-g`, `/Z7` and `/DEBUG`).
+    #[serde(default)]
+    pub debug_info: bool,
+    /// Preprocessor definitions, as `NAME` or `NAME=value`.
+    #[serde(default)]
+    pub defines: Vec<String>,
+    /// Extra compiler flags, passed as-is (so they are specific to a backend).
+    #[serde(default)]
+    pub compile_flags: Vec<String>,
+    /// Extra linker flags, passed as-is. Not used when archiving static libraries.
+    #[serde(default)]
+    pub link_flags: Vec<String>,
+}
+
+impl BuildProfile {
+    /// Names of the profiles that exist without being defined in the solution.
+    pub const BUILTIN: [&'static str; 2] = ["debug", "release"];
+
+    /// The built-in profile named `name`: `debug` (no optimisation, debug info, `DEBUG` defined)
+    /// or `release` (optimised for speed, `NDEBUG` defined).
+    pub fn builtin(name: &str) -> Option<BuildProfile> {
+        match name {
+            "debug" => Some(BuildProfile {
+                name: name.to_string(),
+                optimization: Optimization::None,
+                debug_info: true,
+                defines: vec!["DEBUG".to_string()],
+                ..Default::default()
+            }),
+            "release" => Some(BuildProfile {
+                name: name.to_string(),
+                optimization: Optimization::Speed,
+                debug_info: false,
+                defines: vec!["NDEBUG".to_string()],
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Deserialize, Eq, PartialEq, Clone)]
@@ -14,7 +114,8 @@ pub enum ProjectType {
     Executable,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum TargetArch {
     X86,
     X64,
@@ -22,6 +123,99 @@ pub enum TargetArch {
     ARM64,
 }
 
+impl TargetArch {
+    /// Lowercase name, used for output directories (`output/<arch>/...`) and messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TargetArch::X86 => "x86",
+            TargetArch::X64 => "x64",
+            TargetArch::ARM => "arm",
+            TargetArch::ARM64 => "arm64",
+        }
+    }
+
+    /// The architecture spbuild is running on, if it is one spbuild knows about.
+    pub fn host() -> Option<TargetArch> {
+        match std::env::consts::ARCH {
+            "x86" => Some(TargetArch::X86),
+            "x86_64" => Some(TargetArch::X64),
+            "arm" => Some(TargetArch::ARM),
+            "aarch64" => Some(TargetArch::ARM64),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetPlatform {
+    Windows,
+    Linux,
+    Macos,
+}
+
+impl TargetPlatform {
+    /// Lowercase name, as written in `target_platforms`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TargetPlatform::Windows => "windows",
+            TargetPlatform::Linux => "linux",
+            TargetPlatform::Macos => "macos",
+        }
+    }
+
+    /// The platform spbuild is running on, if it is one spbuild knows about.
+    pub fn host() -> Option<TargetPlatform> {
+        match std::env::consts::OS {
+            "windows" => Some(TargetPlatform::Windows),
+            "linux" => Some(TargetPlatform::Linux),
+            "macos" => Some(TargetPlatform::Macos),
+            _ => None,
+        }
+    }
+}
+
+/// C language standard, translated by each backend (`-std=c11`, `/std:c11`).
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CStandard {
+    C89,
+    C99,
+    C11,
+    C17,
+    C23,
+}
+
+/// C++ language standard, translated by each backend (`-std=c++20`, `/std:c++20`).
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CxxStandard {
+    #[serde(rename = "c++11")]
+    Cxx11,
+    #[serde(rename = "c++14")]
+    Cxx14,
+    #[serde(rename = "c++17")]
+    Cxx17,
+    #[serde(rename = "c++20")]
+    Cxx20,
+    #[serde(rename = "c++23")]
+    Cxx23,
+}
+
+/// Warnings to enable, translated by each backend (`-Wall -Wextra`, `/W4`...).
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WarningLevel {
+    /// No warnings at all.
+    None,
+    /// The compiler's defaults.
+    #[default]
+    Default,
+    /// The commonly useful warnings.
+    All,
+    /// Everything `all` enables and more.
+    Extra,
+}
+
 // Implemented clone for Dependency to allow duplication when needed.
 // TODO: Find a way to not use that if possible.
 #[derive(Deserialize, Clone)]
@@ -37,18 +231,58 @@ pub struct Project {
     pub version: String,
     pub project_type: ProjectType,
     pub target_archs: Vec<TargetArch>,
+    /// Platforms the project is built for. An empty list means every platform.
+    #[serde(default)]
+    pub target_platforms: Vec<TargetPlatform>,
     pub path: PathBuf,
     pub dependencies: Vec<Dependency>,
     pub additional_includes: Vec<PathBuf>,
+    /// Preprocessor definitions, as `NAME` or `NAME=value`. Added to the profile's.
+    #[serde(default)]
+    pub defines: Vec<String>,
+    /// Standard the C sources are compiled with. The compiler's default if not set.
+    #[serde(default)]
+    pub c_standard: Option<CStandard>,
+    /// Standard the C++ sources are compiled with. The compiler's default if not set.
+    #[serde(default)]
+    pub cxx_standard: Option<CxxStandard>,
+    #[serde(default)]
+    pub warning_level: WarningLevel,
+    #[serde(default)]
+    pub warnings_as_errors: bool,
+    /// Overrides the profile's optimisation for this project.
+    #[serde(default)]
+    pub optimization: Option<Optimization>,
+    /// Extra compiler flags, passed as-is after the profile's (so they are specific to a backend).
+    #[serde(default)]
+    pub compile_flags: Vec<String>,
+    /// Extra linker flags, passed as-is after the profile's. Not used when archiving static libraries.
+    #[serde(default)]
+    pub link_flags: Vec<String>,
+    /// Libraries of the system or the toolchain to link with, by name (`pthread`, `m`, `user32`).
+    /// Static libraries pass them on to the projects linking them.
+    #[serde(default)]
+    pub system_libs: Vec<String>,
+    /// Directories searched for `system_libs` (relative to the project path). Passed on like `system_libs`.
+    #[serde(default)]
+    pub library_dirs: Vec<PathBuf>,
+    /// Prebuilt libraries (`.a`, `.so`, `.lib`) to link with, relative to the project path. Passed on like `system_libs`.
+    #[serde(default)]
+    pub prebuilt_libs: Vec<PathBuf>,
+    /// Whether the project is a test executable, run by `spbuild test` rather than `spbuild run`.
+    #[serde(default)]
+    pub test: bool,
 }
 
 
 impl Project {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         name: &str,
         version: &str,
         project_type: ProjectType,
         target_archs: Vec<TargetArch>,
+        target_platforms: Vec<TargetPlatform>,
         path: PathBuf,
         dependencies: Vec<Dependency>,
         additional_includes: Vec<PathBuf>,
@@ -58,9 +292,97 @@ impl Project {
             version: version.to_string(),
             project_type,
             target_archs,
+            target_platforms,
             path,
             dependencies,
             additional_includes,
+            defines: Vec::new(),
+            c_standard: None,
+            cxx_standard: None,
+            warning_level: WarningLevel::Default,
+            warnings_as_errors: false,
+            optimization: None,
+            compile_flags: Vec::new(),
+            link_flags: Vec::new(),
+            system_libs: Vec::new(),
+            library_dirs: Vec::new(),
+            prebuilt_libs: Vec::new(),
+            test: false,
         }
     }
-}
\ No newline at end of file
+
+    /// The optimisation to build the project with: its own, or the profile's.
+    pub fn optimization(&self, profile: &BuildProfile) -> Optimization {
+        self.optimization.unwrap_or(profile.optimization)
+    }
+
+    /// Whether the project is built when targeting `platform`.
+    pub fn targets_platform(&self, platform: TargetPlatform) -> bool {
+        self.target_platforms.is_empty() || self.target_platforms.contains(&platform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_fixtures::{project, solution};
+    use super::{BuildProfile, CStandard, CxxStandard, Optimization, Project, ProjectType, Solution, WarningLevel};
+
+    #[test]
+    fn solution_profiles_add_to_or_replace_the_builtin_ones() {
+        let json = r#"{
+            "name": "sol",
+            "projects": [],
+            "profiles": {
+                "release": { "optimization": "size", "defines": ["SMALL"] },
+                "asan": { "debug_info": true, "compile_flags": ["-fsanitize=address"], "link_flags": ["-fsanitize=address"] }
+            }
+        }"#;
+        let sol: Solution = serde_json::from_str(json).unwrap();
+
+        assert_eq!(sol.profile("debug"), BuildProfile::builtin("debug"));
+        let release = sol.profile("release").unwrap();
+        assert_eq!((release.name.as_str(), release.optimization, release.debug_info), ("release", Optimization::Size, false));
+        assert_eq!(release.defines, ["SMALL"]);
+        let asan = sol.profile("asan").unwrap();
+        assert_eq!((asan.name.as_str(), asan.optimization, asan.debug_info), ("asan", Optimization::None, true));
+        assert_eq!(asan.compile_flags, ["-fsanitize=address"]);
+        assert!(sol.profile("profiling").is_none());
+        assert_eq!(sol.profile_names(), ["debug", "release", "asan"]);
+
+        assert_eq!(solution(Vec::new()).profile_names(), ["debug", "release"]);
+    }
+
+    #[test]
+    fn project_flags_use_a_compiler_agnostic_vocabulary() {
+        let json = r#"{
+            "name": "app", "version": "1.0.0", "project_type": "Executable", "target_archs": ["X64"], "path": "app",
+            "dependencies": [], "additional_includes": [],
+            "defines": ["FOO", "BAR=2"], "c_standard": "c11", "cxx_standard": "c++20", "warning_level": "extra",
+            "warnings_as_errors": true, "optimization": "size", "compile_flags": ["-fno-rtti"], "link_flags": ["-s"]
+        }"#;
+        let app: Project = serde_json::from_str(json).unwrap();
+        assert_eq!(app.defines, ["FOO", "BAR=2"]);
+        assert_eq!((app.c_standard, app.cxx_standard), (Some(CStandard::C11), Some(CxxStandard::Cxx20)));
+        assert_eq!((app.warning_level, app.warnings_as_errors, app.optimization), (WarningLevel::Extra, true, Some(Optimization::Size)));
+        assert_eq!((app.compile_flags, app.link_flags), (vec!["-fno-rtti".to_string()], vec!["-s".to_string()]));
+
+        // Every field is optional
+        let json = r#"{
+            "name": "app", "version": "1.0.0", "project_type": "Executable", "target_archs": ["X64"], "path": "app",
+            "dependencies": [], "additional_includes": []
+        }"#;
+        let app: Project = serde_json::from_str(json).unwrap();
+        assert_eq!((app.c_standard, app.cxx_standard, app.warning_level, app.optimization), (None, None, WarningLevel::Default, None));
+        assert!(serde_json::from_str::<WarningLevel>(r#""pedantic""#).is_err());
+    }
+
+    #[test]
+    fn projects_may_override_the_profile_optimization() {
+        let release = BuildProfile::builtin("release").unwrap();
+        let mut app = project("app", ProjectType::Executable, &[]);
+        assert_eq!(app.optimization(&release), Optimization::Speed);
+        app.optimization = Some(Optimization::Size);
+        assert_eq!(app.optimization(&release), Optimization::Size);
+        assert_eq!(app.optimization(&BuildProfile::builtin("debug").unwrap()), Optimization::Size);
+    }
+}
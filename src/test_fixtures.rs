@@ -0,0 +1,50 @@
+// Projects, solutions and scratch directories shared by the unit tests.
+
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+
+use crate::solution::{Dependency, Project, ProjectType, Solution, TargetArch};
+
+/// A project version 1.0.0 built for X64 from the `name` directory, depending on `deps` at version 1.0.0.
+pub fn project(name: &str, project_type: ProjectType, deps: &[&str]) -> Project {
+    let dependencies = deps
+        .iter()
+        .map(|d| Dependency { name: d.to_string(), version: "1.0.0".to_string(), optional: false })
+        .collect();
+    Project::new(name, "1.0.0", project_type, vec![TargetArch::X64], Vec::new(), PathBuf::from(name), dependencies, Vec::new())
+}
+
+/// A solution named `sol` with the default compiler, toolchain and profiles.
+pub fn solution(projects: Vec<Project>) -> Solution {
+    Solution {
+        name: "sol".to_string(),
+        projects,
+        compiler: None,
+        use_lld: false,
+        toolchain: Default::default(),
+        profiles: Default::default(),
+    }
+}
+
+/// A scratch directory for the test `name`, created empty. The test removes it when done.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("spbuild_{}_test_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A stand-in compiler driver or archiver named `name` in `dir`, writing its arguments into its `-o` output (or the
+/// archive following `rcsD`) and creating the import library requested with `-Wl,--out-implib,`, so commands can be
+/// checked without a real toolchain. It waits `delay` first, letting parallel jobs overlap.
+#[cfg(unix)]
+pub fn stand_in_driver(dir: &std::path::Path, name: &str, delay: std::time::Duration) -> PathBuf {
+    use std::fs::{set_permissions, write, Permissions};
+    use std::os::unix::fs::PermissionsExt;
+
+    let driver = dir.join(name);
+    let script = format!("#!/bin/sh\nsleep {}\nargs=\"$*\"\nwhile [ $# -gt 0 ]; do case \"$1\" in -o|rcsD) out=\"$2\"; shift;; -Wl,--out-implib,*) touch \"${{1#-Wl,--out-implib,}}\";; esac; shift; done\necho \"$args\" > \"$out\"\n", delay.as_secs_f64());
+    write(&driver, script).unwrap();
+    set_permissions(&driver, Permissions::from_mode(0o755)).unwrap();
+    driver
+}